@@ -0,0 +1,248 @@
+//! Implementation of a Gatherer for [cram](https://bitheap.org/cram/) test files
+//!
+//! In a `.t` file, commands are indented by two spaces and start with `$ `, optionally continued
+//! on `> ` lines. They are followed by their expected output, indented the same way, whose last
+//! line can be the expected exit status in brackets, such as `[1]`. Output lines can end with the
+//! annotations of [OutputMatch::Cram]. Lines that are not indented are comments.
+//!
+//! Each command is a test named after the comment above it, and the commands of a file make a
+//! [TestSuite] that runs in a single shell, in which `$TESTDIR` is the directory of the file and
+//! `$TESTTMP` the temporary directory of the shell.
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::gatherer::walker::DefaultWalker;
+use crate::gatherer::{utils, walker, Gatherer};
+use crate::types::output_match::OutputMatch;
+use crate::types::testcase::{Cmd, SharedShell, TestCase, TestSuite, TestSuiteCollection};
+
+/// The files that hold cram tests
+pub(crate) const PATTERNS: &[&str] = &["**/*.t"];
+
+/// The environment of the commands, as set by cram
+const ENV: &[(&str, &str)] = &[
+    ("LANG", "C"),
+    ("LC_ALL", "C"),
+    ("LANGUAGE", "C"),
+    ("TZ", "GMT"),
+    ("COLUMNS", "80"),
+    ("CDPATH", ""),
+];
+
+/// Gather testcases from cram test files
+pub struct CramGatherer<Walker: walker::Walker> {
+    search_dir: String,
+    walker: Walker,
+}
+
+impl CramGatherer<DefaultWalker> {
+    /// Constructs a new [CramGatherer]
+    pub fn new(search_dir: String) -> Self {
+        Self::with_dependencies(search_dir, DefaultWalker::with_patterns(PATTERNS))
+    }
+}
+
+impl<Walker: walker::Walker> CramGatherer<Walker> {
+    fn with_dependencies(search_dir: String, walker: Walker) -> Self {
+        CramGatherer { search_dir, walker }
+    }
+}
+
+impl<Walker: walker::Walker> Gatherer for CramGatherer<Walker> {
+    fn gather(&self) -> Result<TestSuiteCollection> {
+        let mut testsuites = vec![];
+        for path in self.walker.walk(&self.search_dir)? {
+            let name = utils::get_stem(&path, &self.search_dir);
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+            let path = fs::canonicalize(&path)?;
+            let mut env = ENV
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>();
+            if let Some(dir) = path.parent() {
+                env.insert("TESTDIR".to_string(), dir.to_string_lossy().into_owned());
+            }
+            if let Some(file) = path.file_name() {
+                env.insert("TESTFILE".to_string(), file.to_string_lossy().into_owned());
+            }
+
+            testsuites.push(TestSuite {
+                tests: parse_file(&text)
+                    .into_iter()
+                    .map(|test_case| TestCase {
+                        name: format!("{}::{}", name, test_case.name),
+                        env: env.clone(),
+                        ..test_case
+                    })
+                    .collect(),
+                name,
+                services: vec![],
+                shared_shell: Some(SharedShell {
+                    setup: "TESTTMP=$PWD; export TESTTMP".to_string(),
+                    merge_stderr: true,
+                }),
+            });
+        }
+
+        Ok(TestSuiteCollection::new(testsuites))
+    }
+}
+
+/// Find the commands of a cram file, in order
+fn parse_file(text: &str) -> Vec<TestCase> {
+    let mut test_cases: Vec<TestCase> = vec![];
+    let mut comment = None;
+    // Whether the indented lines are the continuation or the output of the last command
+    let mut in_command = false;
+    // Whether the last command has output lines, after which it cannot be continued
+    let mut has_output = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let indented = match line.strip_prefix("  ") {
+            Some(indented) => indented,
+            None => {
+                in_command = false;
+                if !line.trim().is_empty() {
+                    comment = Some(line.trim().trim_end_matches(':').to_string());
+                }
+                continue;
+            }
+        };
+        if let Some(cmd) = indented.strip_prefix("$ ") {
+            test_cases.push(TestCase {
+                name: comment
+                    .clone()
+                    .unwrap_or_else(|| format!("line {}", index + 1)),
+                cmd: cmd.into(),
+                output_match: OutputMatch::Cram,
+                ..Default::default()
+            });
+            in_command = true;
+            has_output = false;
+        } else if let Some(test_case) = test_cases.last_mut().filter(|_| in_command) {
+            match indented.strip_prefix("> ") {
+                Some(continued) if !has_output => {
+                    if let Cmd::Shell(cmd) = &mut test_case.cmd {
+                        cmd.push('\n');
+                        cmd.push_str(continued);
+                    }
+                }
+                _ => {
+                    test_case.stdout.push_str(indented);
+                    test_case.stdout.push('\n');
+                    has_output = true;
+                }
+            }
+        }
+    }
+
+    for test_case in &mut test_cases {
+        test_case.status = utils::take_status(&mut test_case.stdout);
+    }
+    utils::number_duplicates(test_cases.iter_mut().map(|test_case| &mut test_case.name));
+    test_cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_file() {
+        // GIVEN
+        let text = indoc! {r#"
+            Create a file:
+
+              $ echo hello > greeting
+              $ cat greeting \
+              >   greeting
+              hello
+              hello
+
+            Fail:
+
+              $ ls missing
+              ls: *missing* (glob)
+              [2]
+              $ printf 'no newline'
+              no newline (no-eol)
+        "#};
+
+        // WHEN
+        let test_cases = parse_file(text);
+
+        // THEN
+        let test_case = |name: &str, cmd: &str, stdout: &str, status: i32| TestCase {
+            name: name.to_string(),
+            cmd: cmd.into(),
+            stdout: stdout.to_string(),
+            status,
+            output_match: OutputMatch::Cram,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                test_case("Create a file", "echo hello > greeting", "", 0),
+                test_case(
+                    "Create a file [2]",
+                    "cat greeting \\\n  greeting",
+                    "hello\nhello\n",
+                    0
+                ),
+                test_case("Fail", "ls missing", "ls: *missing* (glob)\n", 2),
+                test_case(
+                    "Fail [2]",
+                    "printf 'no newline'",
+                    "no newline (no-eol)\n",
+                    0
+                ),
+            ],
+            test_cases
+        );
+    }
+
+    #[test]
+    fn test_gather() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("basic.t"), "  $ echo hi\n  hi\n").unwrap();
+        let gatherer = CramGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let collection = gatherer.gather().unwrap();
+
+        // THEN
+        let testsuite = &collection.testsuites[0];
+        assert_eq!("basic", testsuite.name);
+        assert_eq!(
+            Some(SharedShell {
+                setup: "TESTTMP=$PWD; export TESTTMP".to_string(),
+                merge_stderr: true,
+            }),
+            testsuite.shared_shell
+        );
+        assert_eq!("basic::line 1", testsuite.tests[0].name);
+        assert_eq!(Cmd::from("echo hi"), testsuite.tests[0].cmd);
+        assert_eq!(
+            fs::canonicalize(dir.path()).unwrap().to_string_lossy(),
+            testsuite.tests[0].env["TESTDIR"]
+        );
+        assert_eq!("C", testsuite.tests[0].env["LC_ALL"]);
+    }
+
+    #[test]
+    fn test_new_calls_with_dependencies() {
+        // GIVEN
+        let walker = DefaultWalker::with_patterns(PATTERNS);
+
+        // WHEN
+        let gatherer = CramGatherer::new(".".to_string());
+
+        // THEN
+        assert_eq!(walker, gatherer.walker);
+    }
+}
@@ -0,0 +1,204 @@
+//! Implementation of a Gatherer for tests stored as directories of files
+//!
+//! Each test is a directory holding a `cmd` file, the command to run through the shell, and
+//! optionally `stdin`, `stdout`, `stderr` and `status` files. The tests of a directory make a
+//! [TestSuite].
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use crate::gatherer::walker::DefaultWalker;
+use crate::gatherer::{utils, walker, Gatherer};
+use crate::types::testcase::{TestCase, TestSuite, TestSuiteCollection};
+
+/// The files that mark a directory as a test
+pub(crate) const PATTERNS: &[&str] = &["**/cmd"];
+
+/// Gather testcases from directories of files
+pub struct DirectoryGatherer<Walker: walker::Walker> {
+    search_dir: String,
+    walker: Walker,
+}
+
+impl DirectoryGatherer<DefaultWalker> {
+    /// Constructs a new [DirectoryGatherer]
+    pub fn new(search_dir: String) -> Self {
+        Self::with_dependencies(search_dir, DefaultWalker::with_patterns(PATTERNS))
+    }
+}
+
+impl<Walker: walker::Walker> DirectoryGatherer<Walker> {
+    fn with_dependencies(search_dir: String, walker: Walker) -> Self {
+        DirectoryGatherer { search_dir, walker }
+    }
+
+    fn get_testcase_from_dir(&self, test_dir: &path::Path, name: String) -> Result<TestCase> {
+        let read = |file: &str| -> Result<Option<String>> {
+            let path = test_dir.join(file);
+            match path.is_file() {
+                true => {
+                    Ok(Some(fs::read_to_string(&path).with_context(|| {
+                        format!("failed to read `{}`", path.display())
+                    })?))
+                }
+                false => Ok(None),
+            }
+        };
+        let status = match read("status")? {
+            Some(status) => status.trim().parse().with_context(|| {
+                format!("invalid status in `{}`", test_dir.join("status").display())
+            })?,
+            None => 0,
+        };
+
+        Ok(TestCase {
+            name,
+            cmd: read("cmd")?.unwrap_or_default().trim_end().into(),
+            stdin: read("stdin")?.unwrap_or_default(),
+            stdout: read("stdout")?.unwrap_or_default(),
+            stderr: read("stderr")?.unwrap_or_default(),
+            status,
+            ..Default::default()
+        })
+    }
+}
+
+impl<Walker: walker::Walker> Gatherer for DirectoryGatherer<Walker> {
+    fn gather(&self) -> Result<TestSuiteCollection> {
+        let mut testsuites: BTreeMap<String, TestSuite> = BTreeMap::new();
+        for cmd in self.walker.walk(&self.search_dir)? {
+            let test_dir = match cmd.parent() {
+                Some(test_dir) if cmd.is_file() => test_dir,
+                _ => continue,
+            };
+            let (suite, test) = utils::get_names(test_dir, &self.search_dir);
+            let test_case = self.get_testcase_from_dir(test_dir, format!("{}::{}", suite, test))?;
+            testsuites
+                .entry(suite.clone())
+                .or_insert_with(|| TestSuite {
+                    name: suite,
+                    ..Default::default()
+                })
+                .tests
+                .push(test_case);
+        }
+
+        Ok(TestSuiteCollection::new(testsuites.into_values()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gather() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let search_dir = dir.path().to_string_lossy().into_owned();
+        for (test, files) in [
+            (
+                "calc/add",
+                &[
+                    ("cmd", "./mycalc\n"),
+                    ("stdin", "2 + 4\n"),
+                    ("stdout", "6\n"),
+                ][..],
+            ),
+            (
+                "calc/error",
+                &[
+                    ("cmd", "./mycalc"),
+                    ("stderr", "error\n"),
+                    ("status", "1\n"),
+                ],
+            ),
+            ("empty", &[("cmd", "true")]),
+        ] {
+            fs::create_dir_all(dir.path().join(test)).unwrap();
+            for (file, content) in files {
+                fs::write(dir.path().join(test).join(file), content).unwrap();
+            }
+        }
+        let gatherer = DirectoryGatherer::new(search_dir);
+
+        // WHEN
+        let collection = gatherer.gather();
+
+        // THEN
+        let top_level = dir.path().file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            TestSuiteCollection {
+                testsuites: vec![
+                    TestSuite {
+                        name: top_level.to_string(),
+                        tests: vec![TestCase {
+                            name: format!("{}::empty", top_level),
+                            cmd: "true".into(),
+                            ..Default::default()
+                        }],
+                        services: vec![],
+                        shared_shell: None,
+                    },
+                    TestSuite {
+                        name: "calc".to_string(),
+                        tests: vec![
+                            TestCase {
+                                name: "calc::add".to_string(),
+                                cmd: "./mycalc".into(),
+                                stdin: "2 + 4\n".to_string(),
+                                stdout: "6\n".to_string(),
+                                ..Default::default()
+                            },
+                            TestCase {
+                                name: "calc::error".to_string(),
+                                cmd: "./mycalc".into(),
+                                stderr: "error\n".to_string(),
+                                status: 1,
+                                ..Default::default()
+                            },
+                        ],
+                        services: vec![],
+                        shared_shell: None,
+                    },
+                ]
+            },
+            collection.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_gather_invalid_status() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("broken")).unwrap();
+        fs::write(dir.path().join("broken/cmd"), "true").unwrap();
+        fs::write(dir.path().join("broken/status"), "zero").unwrap();
+        let gatherer = DirectoryGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let result = gatherer.gather();
+
+        // THEN
+        assert_eq!(
+            format!(
+                "invalid status in `{}`",
+                dir.path().join("broken/status").display()
+            ),
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_new_calls_with_dependencies() {
+        // GIVEN
+        let walker = DefaultWalker::with_patterns(PATTERNS);
+
+        // WHEN
+        let gatherer = DirectoryGatherer::new(".".to_string());
+
+        // THEN
+        assert_eq!(walker, gatherer.walker);
+    }
+}
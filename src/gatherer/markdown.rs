@@ -0,0 +1,377 @@
+//! Implementation of a Gatherer for Markdown documents
+//!
+//! Fenced code blocks tagged `console` or `pharaoh` hold shell sessions: each `$ command` line,
+//! optionally continued by `> ` lines after a line ending with `\`, is followed by the expected
+//! output of the command, whose last line can be the expected exit status in brackets, such as
+//! `[1]`. The stderr of the command is expected along with its stdout, as in a terminal.
+//!
+//! Each command is a test named after the nearest heading above it, and the commands of a document
+//! make a [TestSuite].
+use anyhow::{Context, Result};
+use std::fs;
+use std::ops::Range;
+
+use crate::gatherer::walker::DefaultWalker;
+use crate::gatherer::{utils, walker, Gatherer};
+use crate::types::result::TestReport;
+use crate::types::testcase::{TestCase, TestSuite, TestSuiteCollection};
+
+/// The files that hold Markdown documents
+pub(crate) const PATTERNS: &[&str] = &["**/*.md"];
+
+/// The tags of the code blocks that hold shell sessions
+const TAGS: &[&str] = &["console", "pharaoh"];
+
+/// Gather testcases from the shell sessions of Markdown documents
+pub struct MarkdownGatherer<Walker: walker::Walker> {
+    search_dir: String,
+    walker: Walker,
+}
+
+impl MarkdownGatherer<DefaultWalker> {
+    /// Constructs a new [MarkdownGatherer]
+    pub fn new(search_dir: String) -> Self {
+        Self::with_dependencies(search_dir, DefaultWalker::with_patterns(PATTERNS))
+    }
+}
+
+impl<Walker: walker::Walker> MarkdownGatherer<Walker> {
+    fn with_dependencies(search_dir: String, walker: Walker) -> Self {
+        MarkdownGatherer { search_dir, walker }
+    }
+}
+
+impl<Walker: walker::Walker> Gatherer for MarkdownGatherer<Walker> {
+    fn gather(&self) -> Result<TestSuiteCollection> {
+        let mut testsuites = vec![];
+        for path in self.walker.walk(&self.search_dir)? {
+            let name = utils::get_stem(&path, &self.search_dir);
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            let tests = parse_document(&text)
+                .into_iter()
+                .map(|command| TestCase {
+                    name: format!("{}::{}", name, command.name),
+                    cmd: format!("exec 2>&1\n{}", command.cmd).into(),
+                    stdout: command.stdout,
+                    status: command.status,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+            if !tests.is_empty() {
+                testsuites.push(TestSuite {
+                    name,
+                    tests,
+                    services: vec![],
+                    shared_shell: None,
+                });
+            }
+        }
+
+        Ok(TestSuiteCollection::new(testsuites))
+    }
+
+    /// Replace the expected output of the failed commands with their actual output
+    fn bless(&self, report: &TestReport) -> Result<()> {
+        for path in self.walker.walk(&self.search_dir)? {
+            let name = utils::get_stem(&path, &self.search_dir);
+            let results = match report.testsuites.iter().find(|suite| suite.name == name) {
+                Some(suite) => &suite.results,
+                None => continue,
+            };
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+            let mut lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+            for command in parse_document(&text).iter().rev() {
+                let test_name = format!("{}::{}", name, command.name);
+                let result = results.iter().find(|result| result.name == test_name);
+                if let Some(result) =
+                    result.filter(|result| !result.is_successful() && result.error.is_none())
+                {
+                    let mut output = result
+                        .actual_stdout
+                        .lines()
+                        .map(str::to_string)
+                        .collect::<Vec<_>>();
+                    if result.actual_status != 0 {
+                        output.push(format!("[{}]", result.actual_status));
+                    }
+                    lines.splice(command.output.clone(), output);
+                }
+            }
+
+            let mut blessed = lines.join("\n");
+            if text.ends_with('\n') {
+                blessed.push('\n');
+            }
+            if blessed != text {
+                fs::write(&path, blessed)
+                    .with_context(|| format!("failed to write `{}`", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A command of a shell session in a Markdown document
+#[derive(Eq, PartialEq, Debug)]
+struct Command {
+    name: String,
+    cmd: String,
+    stdout: String,
+    status: i32,
+    /// The indexes of the lines of the expected output and status in the document
+    output: Range<usize>,
+}
+
+/// Find the commands of the shell sessions of a document, in order
+fn parse_document(text: &str) -> Vec<Command> {
+    let mut commands: Vec<Command> = vec![];
+    let mut heading = None;
+    // The fence of the current block, and whether it holds a shell session
+    let mut block: Option<(&str, bool)> = None;
+    // Whether the lines are the continuation or the output of the last command
+    let mut in_command = false;
+
+    for (index, line) in text.lines().enumerate() {
+        match block {
+            None => {
+                if let Some(title) = line.strip_prefix('#') {
+                    heading = Some(title.trim_start_matches('#').trim().to_string());
+                } else if let Some((fence, tag)) = utils::open_fence(line) {
+                    block = Some((fence, TAGS.contains(&tag)));
+                }
+            }
+            Some((fence, _)) if utils::closes_fence(line, fence) => {
+                block = None;
+                in_command = false;
+            }
+            Some((_, false)) => {}
+            Some((_, true)) => {
+                if let Some(cmd) = line.strip_prefix("$ ") {
+                    commands.push(Command {
+                        name: heading
+                            .clone()
+                            .unwrap_or_else(|| format!("line {}", index + 1)),
+                        cmd: cmd.to_string(),
+                        stdout: String::new(),
+                        status: 0,
+                        output: index + 1..index + 1,
+                    });
+                    in_command = true;
+                } else if let Some(command) = commands.last_mut().filter(|_| in_command) {
+                    match line.strip_prefix("> ") {
+                        Some(continued)
+                            if command.output.is_empty() && command.cmd.ends_with('\\') =>
+                        {
+                            command.cmd.push('\n');
+                            command.cmd.push_str(continued);
+                            command.output = index + 1..index + 1;
+                        }
+                        _ => {
+                            command.stdout.push_str(line);
+                            command.stdout.push('\n');
+                            command.output.end = index + 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for command in &mut commands {
+        command.status = utils::take_status(&mut command.stdout);
+    }
+    utils::number_duplicates(commands.iter_mut().map(|command| &mut command.name));
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    use crate::types::result::{TestResult, TestSuiteResult};
+
+    const DOCUMENT: &str = indoc! {r#"
+        # Usage
+
+        ```console
+        $ echo hello
+        hello
+        $ printf 'a\nb\n'; false
+        a
+        b
+        [1]
+        ```
+
+        ## Continued lines
+
+        ~~~pharaoh
+        $ echo one \
+        > two
+        one two
+        ~~~
+
+        ```sh
+        # not a heading
+        $ not a command
+        ```
+    "#};
+
+    #[test]
+    fn test_parse_document() {
+        // WHEN
+        let commands = parse_document(DOCUMENT);
+
+        // THEN
+        assert_eq!(
+            vec![
+                Command {
+                    name: "Usage".to_string(),
+                    cmd: "echo hello".to_string(),
+                    stdout: "hello\n".to_string(),
+                    status: 0,
+                    output: 4..5,
+                },
+                Command {
+                    name: "Usage [2]".to_string(),
+                    cmd: "printf 'a\\nb\\n'; false".to_string(),
+                    stdout: "a\nb\n".to_string(),
+                    status: 1,
+                    output: 6..9,
+                },
+                Command {
+                    name: "Continued lines".to_string(),
+                    cmd: "echo one \\\ntwo".to_string(),
+                    stdout: "one two\n".to_string(),
+                    status: 0,
+                    output: 16..17,
+                },
+            ],
+            commands
+        );
+    }
+
+    #[test]
+    fn test_parse_document_output_like_continuation() {
+        // GIVEN
+        let text = indoc! {r#"
+            ```console
+            $ printf '> prompt\n'
+            > prompt
+            ```
+        "#};
+
+        // WHEN
+        let commands = parse_document(text);
+
+        // THEN
+        assert_eq!(
+            vec![Command {
+                name: "line 2".to_string(),
+                cmd: "printf '> prompt\\n'".to_string(),
+                stdout: "> prompt\n".to_string(),
+                status: 0,
+                output: 2..3,
+            }],
+            commands
+        );
+    }
+
+    #[test]
+    fn test_gather() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), DOCUMENT).unwrap();
+        fs::write(dir.path().join("empty.md"), "# Nothing to test\n").unwrap();
+        let gatherer = MarkdownGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let collection = gatherer.gather().unwrap();
+
+        // THEN
+        assert_eq!(1, collection.testsuites.len());
+        assert_eq!(
+            TestCase {
+                name: "README::Usage".to_string(),
+                cmd: "exec 2>&1\necho hello".into(),
+                stdout: "hello\n".to_string(),
+                ..Default::default()
+            },
+            collection.testsuites[0].tests[0]
+        );
+    }
+
+    #[test]
+    fn test_gather_skips_dependencies() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), DOCUMENT).unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/dep")).unwrap();
+        fs::write(dir.path().join("node_modules/dep/README.md"), DOCUMENT).unwrap();
+        let gatherer = MarkdownGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let collection = gatherer.gather().unwrap();
+
+        // THEN
+        assert_eq!(1, collection.testsuites.len());
+        assert_eq!("README", collection.testsuites[0].name);
+    }
+
+    #[test]
+    fn test_bless() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        fs::write(&path, DOCUMENT).unwrap();
+        let gatherer = MarkdownGatherer::new(dir.path().to_string_lossy().into_owned());
+        let result = |name: &str, stdout: &str, status: i32| TestResult {
+            name: format!("README::{}", name),
+            actual_stdout: stdout.to_string(),
+            actual_status: status,
+            ..TestResult::from_error(TestCase::default(), anyhow::anyhow!("unused"))
+        };
+        let report = TestReport {
+            testsuites: vec![TestSuiteResult {
+                name: "README".to_string(),
+                results: vec![
+                    TestResult {
+                        error: None,
+                        ..result("Usage", "hi\nthere\n", 0)
+                    },
+                    TestResult {
+                        error: None,
+                        ..result("Usage [2]", "a\n", 2)
+                    },
+                    result("Continued lines", "ignored because of the error\n", 0),
+                ],
+            }],
+        };
+
+        // WHEN
+        gatherer.bless(&report).unwrap();
+
+        // THEN
+        assert_eq!(
+            DOCUMENT
+                .replace("$ echo hello\nhello\n", "$ echo hello\nhi\nthere\n")
+                .replace("a\nb\n[1]\n", "a\n[2]\n"),
+            fs::read_to_string(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_new_calls_with_dependencies() {
+        // GIVEN
+        let walker = DefaultWalker::with_patterns(PATTERNS);
+
+        // WHEN
+        let gatherer = MarkdownGatherer::new(".".to_string());
+
+        // THEN
+        assert_eq!(walker, gatherer.walker);
+    }
+}
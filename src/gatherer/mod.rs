@@ -1,13 +1,142 @@
 //! [TestSuiteCollection] gathering
+use crate::types::result::TestReport;
 use crate::types::testcase::TestSuiteCollection;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::str::FromStr;
 
+pub mod cram;
+pub mod directory;
+pub mod markdown;
+pub mod trycmd;
 pub mod yaml;
+pub use cram::CramGatherer;
+pub use directory::DirectoryGatherer;
+pub use markdown::MarkdownGatherer;
+pub use trycmd::TrycmdGatherer;
 pub use yaml::YamlGatherer;
 
+use walker::{DefaultWalker, Walker};
+
+mod utils;
+pub(crate) mod walker;
+
 /// A trait to regroup all structs able to gather [TestSuiteCollection]s from somewhere
 #[mockall::automock]
 pub trait Gatherer {
     /// Gather testcases from an implementation-specific place to produce a [TestSuiteCollection]
     fn gather(&self) -> Result<TestSuiteCollection>;
+
+    /// Write the actual outputs of the failed tests of `report` back into the tests, if the
+    /// implementation supports it
+    fn bless(&self, _report: &TestReport) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<G: Gatherer + ?Sized> Gatherer for Box<G> {
+    fn gather(&self) -> Result<TestSuiteCollection> {
+        (**self).gather()
+    }
+
+    fn bless(&self, report: &TestReport) -> Result<()> {
+        (**self).bless(report)
+    }
+}
+
+/// The layouts in which tests can be stored
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Format {
+    /// YAML, JSON or TOML files, gathered by a [YamlGatherer]
+    Yaml,
+    /// Directories of files, gathered by a [DirectoryGatherer]
+    Directory,
+    /// Shell sessions in Markdown documents, gathered by a [MarkdownGatherer]
+    Markdown,
+    /// Cram test files, gathered by a [CramGatherer]
+    Cram,
+    /// Trycmd case files, gathered by a [TrycmdGatherer]
+    Trycmd,
+    /// Whichever of the others is found in the search directory
+    Auto,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "yaml" => Ok(Format::Yaml),
+            "dir" => Ok(Format::Directory),
+            "md" => Ok(Format::Markdown),
+            "cram" => Ok(Format::Cram),
+            "trycmd" => Ok(Format::Trycmd),
+            "auto" => Ok(Format::Auto),
+            _ => bail!("unknown format `{}`", format),
+        }
+    }
+}
+
+impl Format {
+    /// Construct the [Gatherer] of this format for `search_dir`
+    ///
+    /// In [Format::Auto], tests are gathered from YAML, JSON or TOML files if there are some, then
+    /// from directories of files, then from cram files. Markdown documents and trycmd case files
+    /// are only gathered on demand, as most Markdown and TOML files are not tests.
+    pub fn gatherer(self, search_dir: String) -> Result<Box<dyn Gatherer>> {
+        let format = match self {
+            Format::Auto => Self::detect(&search_dir)?,
+            format => format,
+        };
+        Ok(match format {
+            Format::Directory => Box::new(DirectoryGatherer::new(search_dir)),
+            Format::Markdown => Box::new(MarkdownGatherer::new(search_dir)),
+            Format::Cram => Box::new(CramGatherer::new(search_dir)),
+            Format::Trycmd => Box::new(TrycmdGatherer::new(search_dir)),
+            _ => Box::new(YamlGatherer::new(search_dir)),
+        })
+    }
+
+    fn detect(search_dir: &str) -> Result<Format> {
+        let found = |patterns| -> Result<bool> {
+            Ok(!DefaultWalker::with_patterns(patterns)
+                .walk(search_dir)?
+                .is_empty())
+        };
+        if found(yaml::PATTERNS)? {
+            Ok(Format::Yaml)
+        } else if found(directory::PATTERNS)? {
+            Ok(Format::Directory)
+        } else if found(cram::PATTERNS)? {
+            Ok(Format::Cram)
+        } else {
+            Ok(Format::Yaml)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_detect() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let search_dir = dir.path().to_string_lossy().into_owned();
+        fs::write(dir.path().join("foo.t"), "").unwrap();
+        let cram = Format::detect(&search_dir).unwrap();
+        fs::create_dir(dir.path().join("add")).unwrap();
+        fs::write(dir.path().join("add/cmd"), "true").unwrap();
+
+        // WHEN
+        let directories = Format::detect(&search_dir).unwrap();
+        fs::write(dir.path().join("foo.yml"), "").unwrap();
+        let both = Format::detect(&search_dir).unwrap();
+
+        // THEN
+        assert_eq!(Format::Cram, cram);
+        assert_eq!(Format::Directory, directories);
+        assert_eq!(Format::Yaml, both);
+    }
 }
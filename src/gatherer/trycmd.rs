@@ -0,0 +1,665 @@
+//! Implementation of a Gatherer for [trycmd](https://docs.rs/trycmd) case files
+//!
+//! A `.toml` file is a single test, whose command is given by `bin.name` or `bin.path`, relative
+//! to the file, and `args`. As with trycmd, `bin.name` is a binary of the Cargo project, found with
+//! `CARGO_BIN_EXE_<name>` or in its `target/debug` or `target/release` directory, and only looked
+//! up in the `PATH` when it is not built. Its stdin can be given inline or in a sibling
+//! `.stdin` file, and its expected outputs are in sibling `.stdout` and `.stderr` files. The tests
+//! of a directory make a [TestSuite].
+//!
+//! A `.trycmd` file holds shell sessions, either as a whole or in code blocks tagged `console` or
+//! `trycmd`: each `$ command` line can be followed by a `? status` line, then by its expected
+//! output, stdout and stderr together. The command is split into arguments, and its program found
+//! as for `bin.name`. Each command is a test named after the nearest heading above it, and the
+//! commands of a file make a [TestSuite].
+//!
+//! In both, expected outputs are matched with the wildcards of [OutputMatch::Trycmd].
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::iter;
+use std::path::Path;
+
+use crate::gatherer::walker::DefaultWalker;
+use crate::gatherer::{utils, walker, Gatherer};
+use crate::types::output_match::OutputMatch;
+use crate::types::testcase::{Cmd, TestCase, TestSuite, TestSuiteCollection};
+
+/// The files that hold trycmd cases
+///
+/// Other TOML files, such as `Cargo.toml` or the TOML test files of the YAML format, are left
+/// out. The TOML files without a `bin` table are not cases either, and are skipped once read.
+pub(crate) const PATTERNS: &[&str] = &[
+    "**/*.toml",
+    "**/*.trycmd",
+    "!**/Cargo.toml",
+    "!**/*.pharaoh.toml",
+];
+
+/// The profiles whose target directory holds the binaries of `bin.name`, in order of preference
+const PROFILES: &[&str] = &["debug", "release"];
+
+/// The command running the arguments that follow it, with stderr written to stdout
+const MERGE_STDERR: &[&str] = &["/bin/sh", "-c", "exec \"$0\" \"$@\" 2>&1"];
+
+/// The tags of the code blocks that hold shell sessions
+const TAGS: &[&str] = &["console", "trycmd"];
+
+/// Gather testcases from trycmd case files
+pub struct TrycmdGatherer<Walker: walker::Walker> {
+    search_dir: String,
+    walker: Walker,
+}
+
+impl TrycmdGatherer<DefaultWalker> {
+    /// Constructs a new [TrycmdGatherer]
+    pub fn new(search_dir: String) -> Self {
+        Self::with_dependencies(search_dir, DefaultWalker::with_patterns(PATTERNS))
+    }
+}
+
+impl<Walker: walker::Walker> TrycmdGatherer<Walker> {
+    fn with_dependencies(search_dir: String, walker: Walker) -> Self {
+        TrycmdGatherer { search_dir, walker }
+    }
+
+    /// The test of a `.toml` file, unless it is skipped or is not a case
+    fn get_testcase_from_toml(&self, path: &Path, name: String) -> Result<Option<TestCase>> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let value: toml::Value = toml::from_str(&text)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        if !value.get("bin").is_some_and(toml::Value::is_table) {
+            return Ok(None);
+        }
+        let case: TomlCase = value
+            .try_into()
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        let status = match &case.status {
+            None => 0,
+            Some(Status::Code { code }) => *code,
+            Some(Status::Name(status)) => match parse_status(status)
+                .with_context(|| format!("invalid status in `{}`", path.display()))?
+            {
+                Some(status) => status,
+                None => return Ok(None),
+            },
+        };
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let bin = match (case.bin.name, case.bin.path) {
+            (Some(name), _) => find_bin(name, dir, env::var_os),
+            (None, Some(bin)) => dir.join(bin).to_string_lossy().into_owned(),
+            (None, None) => bail!("`{}` has no `bin.name` or `bin.path`", path.display()),
+        };
+        let args = match case.args {
+            Args::Line(args) => shlex::split(&args)
+                .with_context(|| format!("invalid `args` in `{}`", path.display()))?,
+            Args::List(args) => args,
+        };
+        let read = |extension: &str| -> Result<Option<String>> {
+            let path = path.with_extension(extension);
+            match path.is_file() {
+                true => {
+                    Ok(Some(fs::read_to_string(&path).with_context(|| {
+                        format!("failed to read `{}`", path.display())
+                    })?))
+                }
+                false => Ok(None),
+            }
+        };
+
+        Ok(Some(TestCase {
+            name,
+            cmd: Cmd::Argv(std::iter::once(bin).chain(args).collect()),
+            env: case.env.add,
+            stdin: match case.stdin {
+                Some(stdin) => stdin,
+                None => read("stdin")?.unwrap_or_default(),
+            },
+            stdout: read("stdout")?.unwrap_or_default(),
+            stderr: read("stderr")?.unwrap_or_default(),
+            output_match: OutputMatch::Trycmd,
+            status,
+            workdir: case
+                .fs
+                .cwd
+                .map(|cwd| dir.join(cwd).to_string_lossy().into_owned()),
+            ..Default::default()
+        }))
+    }
+}
+
+/// The program of `bin.name`: the binary that Cargo built, or else the name, to look up in the
+/// `PATH`
+///
+/// The binary is given by `CARGO_BIN_EXE_<name>` when Cargo runs the tests, or found in the
+/// `target` directory of the case file or of one of its ancestors. The environment variables are
+/// read with `var_os`, i.e. [env::var_os] outside of the tests.
+fn find_bin(name: String, dir: &Path, var_os: impl Fn(String) -> Option<OsString>) -> String {
+    if let Some(bin) = var_os(format!("CARGO_BIN_EXE_{}", name)) {
+        return bin.to_string_lossy().into_owned();
+    }
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    for ancestor in dir.ancestors() {
+        for profile in PROFILES {
+            let bin = ancestor.join("target").join(profile).join(&name);
+            if bin.is_file() {
+                return bin.to_string_lossy().into_owned();
+            }
+        }
+    }
+    name
+}
+
+impl<Walker: walker::Walker> Gatherer for TrycmdGatherer<Walker> {
+    fn gather(&self) -> Result<TestSuiteCollection> {
+        let mut testsuites: BTreeMap<String, TestSuite> = BTreeMap::new();
+        for path in self.walker.walk(&self.search_dir)? {
+            let (suite, tests) = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => {
+                    let (suite, test) =
+                        utils::get_names(&path.with_extension(""), &self.search_dir);
+                    let name = format!("{}::{}", suite, test);
+                    let tests = self.get_testcase_from_toml(&path, name)?;
+                    (suite, tests.into_iter().collect())
+                }
+                _ => {
+                    let suite = utils::get_stem(&path, &self.search_dir);
+                    let text = fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read `{}`", path.display()))?;
+                    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+                    let tests = parse_session(&text, dir)
+                        .with_context(|| format!("failed to parse `{}`", path.display()))?
+                        .into_iter()
+                        .map(|test_case| TestCase {
+                            name: format!("{}::{}", suite, test_case.name),
+                            ..test_case
+                        })
+                        .collect::<Vec<_>>();
+                    (suite, tests)
+                }
+            };
+            if !tests.is_empty() {
+                testsuites
+                    .entry(suite.clone())
+                    .or_insert_with(|| TestSuite {
+                        name: suite,
+                        ..Default::default()
+                    })
+                    .tests
+                    .extend(tests);
+            }
+        }
+
+        Ok(TestSuiteCollection::new(testsuites.into_values()))
+    }
+}
+
+/// A `.toml` case file, of which the fields that Pharaoh does not support are ignored
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TomlCase {
+    bin: Bin,
+    args: Args,
+    env: Env,
+    stdin: Option<String>,
+    status: Option<Status>,
+    fs: Fs,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Bin {
+    name: Option<String>,
+    path: Option<String>,
+}
+
+/// The arguments of the command, as a list or as a single line split like the shell does
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Args {
+    Line(String),
+    List(Vec<String>),
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args::List(vec![])
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Env {
+    add: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Status {
+    Code { code: i32 },
+    Name(String),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Fs {
+    cwd: Option<String>,
+}
+
+/// The exit status named by trycmd, or `None` if the test is skipped
+fn parse_status(status: &str) -> Result<Option<i32>> {
+    match status {
+        "success" => Ok(Some(0)),
+        "skipped" => Ok(None),
+        "failed" | "interrupted" => bail!(
+            "status `{}` is not supported, give an exit code instead",
+            status
+        ),
+        _ => match status.parse() {
+            Ok(code) => Ok(Some(code)),
+            Err(_) => bail!("unknown status `{}`", status),
+        },
+    }
+}
+
+/// Find the commands of the shell sessions of a `.trycmd` file, in order, except the skipped ones
+///
+/// As with trycmd, the command lines are split like the shell does rather than run by it, and
+/// their program is looked up with [find_bin] from `dir`, the directory of the file.
+fn parse_session(text: &str, dir: &Path) -> Result<Vec<TestCase>> {
+    // The files without code blocks are a single session
+    let bare = !text.lines().any(|line| utils::open_fence(line).is_some());
+    let mut test_cases: Vec<Option<TestCase>> = vec![];
+    let mut heading = None;
+    // The fence of the current block, and whether it holds a shell session
+    let mut block: Option<(&str, bool)> = None;
+    // Whether the lines are the continuation or the output of the last command
+    let mut in_command = false;
+    // Whether the last command has a status or output lines, after which it cannot be continued
+    let mut has_output = false;
+
+    for (index, line) in text.lines().enumerate() {
+        if !bare {
+            match block {
+                None => {
+                    if let Some(title) = line.strip_prefix('#') {
+                        heading = Some(title.trim_start_matches('#').trim().to_string());
+                    } else if let Some((fence, tag)) = utils::open_fence(line) {
+                        block = Some((fence, TAGS.contains(&tag)));
+                    }
+                    continue;
+                }
+                Some((fence, _)) if utils::closes_fence(line, fence) => {
+                    block = None;
+                    in_command = false;
+                    continue;
+                }
+                Some((_, false)) => continue,
+                Some((_, true)) => {}
+            }
+        }
+
+        if let Some(cmd) = line.strip_prefix("$ ") {
+            test_cases.push(Some(TestCase {
+                name: heading
+                    .clone()
+                    .unwrap_or_else(|| format!("line {}", index + 1)),
+                cmd: cmd.into(),
+                output_match: OutputMatch::Trycmd,
+                ..Default::default()
+            }));
+            in_command = true;
+            has_output = false;
+        } else if let Some(status) = line
+            .strip_prefix("? ")
+            .filter(|_| in_command && !has_output)
+        {
+            let status = parse_status(status.trim())
+                .with_context(|| format!("invalid status on line {}", index + 1))?;
+            if let Some(last) = test_cases.last_mut() {
+                match (status, last.as_mut()) {
+                    (Some(status), Some(test_case)) => test_case.status = status,
+                    (Some(_), None) => {}
+                    (None, _) => *last = None,
+                }
+            }
+            has_output = true;
+        } else if let Some(Some(test_case)) = test_cases.last_mut().filter(|_| in_command) {
+            match line.strip_prefix("> ") {
+                Some(continued) if !has_output => {
+                    if let Cmd::Shell(cmd) = &mut test_case.cmd {
+                        cmd.push('\n');
+                        cmd.push_str(continued);
+                    }
+                }
+                _ => {
+                    test_case.stdout.push_str(line);
+                    test_case.stdout.push('\n');
+                    has_output = true;
+                }
+            }
+        }
+    }
+
+    let mut test_cases = test_cases.into_iter().flatten().collect::<Vec<_>>();
+    for test_case in &mut test_cases {
+        if let Cmd::Shell(line) = &test_case.cmd {
+            let mut words = shlex::split(line)
+                .filter(|words| !words.is_empty())
+                .with_context(|| format!("invalid command `{}`", line))?;
+            let bin = find_bin(words.remove(0), dir, env::var_os);
+            let argv = MERGE_STDERR.iter().map(ToString::to_string);
+            test_case.cmd = Cmd::Argv(argv.chain(iter::once(bin)).chain(words).collect());
+        }
+    }
+    utils::number_duplicates(test_cases.iter_mut().map(|test_case| &mut test_case.name));
+    Ok(test_cases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_session() {
+        // GIVEN
+        let text = indoc! {r#"
+            # Usage
+
+            ```console
+            $ mycmd --help
+            Usage: mycmd [..]
+            ...
+            $ mycmd --bad
+            ? 2
+            error: unexpected argument
+            ```
+
+            ```trycmd
+            $ mycmd --slow
+            ? skipped
+            $ mycmd one \
+            > two
+            ```
+        "#};
+
+        // WHEN
+        let test_cases = parse_session(text, Path::new(".")).unwrap();
+
+        // THEN
+        let test_case = |name: &str, cmd: &[&str], stdout: &str, status: i32| TestCase {
+            name: name.to_string(),
+            cmd: Cmd::Argv(
+                MERGE_STDERR
+                    .iter()
+                    .chain(cmd)
+                    .map(ToString::to_string)
+                    .collect(),
+            ),
+            stdout: stdout.to_string(),
+            status,
+            output_match: OutputMatch::Trycmd,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                test_case("Usage", &["mycmd", "--help"], "Usage: mycmd [..]\n...\n", 0),
+                test_case(
+                    "Usage [2]",
+                    &["mycmd", "--bad"],
+                    "error: unexpected argument\n",
+                    2
+                ),
+                test_case("Usage [3]", &["mycmd", "one", "two"], "", 0),
+            ],
+            test_cases
+        );
+    }
+
+    #[test]
+    fn test_parse_session_bare() {
+        // GIVEN
+        let text = "$ echo hi\nhi\n$ false\n? 1\n";
+
+        // WHEN
+        let test_cases = parse_session(text, Path::new(".")).unwrap();
+
+        // THEN
+        assert_eq!(2, test_cases.len());
+        assert_eq!("line 1", test_cases[0].name);
+        assert_eq!("hi\n", test_cases[0].stdout);
+        assert_eq!("line 3", test_cases[1].name);
+        assert_eq!(1, test_cases[1].status);
+    }
+
+    #[test]
+    fn test_parse_session_finds_bin() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::create_dir(dir.path().join("tests")).unwrap();
+        fs::write(dir.path().join("target/debug/mycmd"), "").unwrap();
+        let tests_dir = dir.path().join("tests");
+
+        // WHEN
+        let valid = parse_session("$ mycmd --name 'a b'\n", &tests_dir);
+        let invalid = parse_session("$ 'unbalanced\n", &tests_dir);
+
+        // THEN
+        let bin = fs::canonicalize(dir.path())
+            .unwrap()
+            .join("target/debug/mycmd");
+        let argv = MERGE_STDERR
+            .iter()
+            .map(ToString::to_string)
+            .chain(vec![
+                bin.to_string_lossy().into_owned(),
+                "--name".to_string(),
+                "a b".to_string(),
+            ])
+            .collect();
+        assert_eq!(Cmd::Argv(argv), valid.unwrap()[0].cmd);
+        assert_eq!(
+            "invalid command `'unbalanced`",
+            invalid.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_session_unsupported_status() {
+        // WHEN
+        let result = parse_session("$ false\n? failed\n", Path::new("."));
+
+        // THEN
+        assert_eq!(
+            "invalid status on line 2: status `failed` is not supported, give an exit code instead",
+            format!("{:#}", result.unwrap_err())
+        );
+    }
+
+    #[test]
+    fn test_gather() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let search_dir = dir.path().to_string_lossy().into_owned();
+        fs::create_dir(dir.path().join("cmd")).unwrap();
+        for (file, content) in [
+            (
+                "cmd/help.toml",
+                "bin.name = \"mycmd\"\nargs = \"--help 'a b'\"\nstatus.code = 1\n",
+            ),
+            ("cmd/help.stdout", "Usage: [..]\n"),
+            (
+                "cmd/stdin.toml",
+                "bin.path = \"mycmd\"\nargs = [\"-\"]\n[env.add]\nLANG = \"C\"\n[fs]\ncwd = \"data\"\n",
+            ),
+            ("cmd/stdin.stdin", "input\n"),
+            ("cmd/stdin.stderr", "warning\n"),
+            ("cmd/slow.toml", "bin.name = \"mycmd\"\nstatus = \"skipped\"\n"),
+            ("cmd/session.trycmd", "$ mycmd\n"),
+        ] {
+            fs::write(dir.path().join(file), content).unwrap();
+        }
+        let gatherer = TrycmdGatherer::new(search_dir);
+
+        // WHEN
+        let collection = gatherer.gather().unwrap();
+
+        // THEN
+        let cmd_dir = dir.path().join("cmd");
+        assert_eq!(
+            TestSuiteCollection {
+                testsuites: vec![
+                    TestSuite {
+                        name: "cmd".to_string(),
+                        tests: vec![
+                            TestCase {
+                                name: "cmd::help".to_string(),
+                                cmd: Cmd::Argv(vec![
+                                    "mycmd".to_string(),
+                                    "--help".to_string(),
+                                    "a b".to_string()
+                                ]),
+                                stdout: "Usage: [..]\n".to_string(),
+                                output_match: OutputMatch::Trycmd,
+                                status: 1,
+                                ..Default::default()
+                            },
+                            TestCase {
+                                name: "cmd::stdin".to_string(),
+                                cmd: Cmd::Argv(vec![
+                                    cmd_dir.join("mycmd").to_string_lossy().into_owned(),
+                                    "-".to_string()
+                                ]),
+                                env: vec![("LANG".to_string(), "C".to_string())]
+                                    .into_iter()
+                                    .collect(),
+                                stdin: "input\n".to_string(),
+                                stderr: "warning\n".to_string(),
+                                output_match: OutputMatch::Trycmd,
+                                workdir: Some(cmd_dir.join("data").to_string_lossy().into_owned()),
+                                ..Default::default()
+                            },
+                        ],
+                        services: vec![],
+                        shared_shell: None,
+                    },
+                    TestSuite {
+                        name: "cmd/session".to_string(),
+                        tests: vec![TestCase {
+                            name: "cmd/session::line 1".to_string(),
+                            cmd: Cmd::Argv(
+                                MERGE_STDERR
+                                    .iter()
+                                    .chain(&["mycmd"])
+                                    .map(ToString::to_string)
+                                    .collect()
+                            ),
+                            output_match: OutputMatch::Trycmd,
+                            ..Default::default()
+                        }],
+                        services: vec![],
+                        shared_shell: None,
+                    },
+                ]
+            },
+            collection
+        );
+    }
+
+    #[test]
+    fn test_find_bin() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let cmd_dir = dir.path().join("tests/cmd");
+        fs::create_dir_all(&cmd_dir).unwrap();
+        for profile in ["debug", "release"] {
+            fs::create_dir_all(dir.path().join("target").join(profile)).unwrap();
+            fs::write(dir.path().join("target").join(profile).join("built"), "").unwrap();
+        }
+        fs::write(dir.path().join("target/release/released"), "").unwrap();
+        let var_os = |key: String| match key.as_str() {
+            "CARGO_BIN_EXE_from-cargo" => Some(OsString::from("/cargo/bin")),
+            _ => None,
+        };
+
+        // WHEN
+        let built = find_bin("built".to_string(), &cmd_dir, var_os);
+        let released = find_bin("released".to_string(), &cmd_dir, var_os);
+        let from_cargo = find_bin("from-cargo".to_string(), &cmd_dir, var_os);
+        let installed = find_bin("not-built".to_string(), &cmd_dir, var_os);
+
+        // THEN
+        let target = fs::canonicalize(dir.path()).unwrap().join("target");
+        assert_eq!(target.join("debug/built").to_string_lossy(), built);
+        assert_eq!(target.join("release/released").to_string_lossy(), released);
+        assert_eq!("/cargo/bin", from_cargo);
+        assert_eq!("not-built", installed);
+    }
+
+    #[test]
+    fn test_gather_no_bin() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("help.toml");
+        fs::write(&path, "args = \"--help\"\n[bin]\n").unwrap();
+        let gatherer = TrycmdGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let result = gatherer.gather();
+
+        // THEN
+        assert_eq!(
+            format!("`{}` has no `bin.name` or `bin.path`", path.display()),
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_gather_skips_other_toml_files() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        for dir_name in ["target/debug", "node_modules/dep", ".github"] {
+            fs::create_dir_all(dir.path().join(dir_name)).unwrap();
+        }
+        let case = "bin.name = \"mycmd\"\n";
+        for (file, content) in [
+            (
+                "Cargo.toml",
+                "[package]\nname = \"mycmd\"\n[[bin]]\nname = \"mycmd\"\n",
+            ),
+            ("rustfmt.toml", "edition = \"2018\"\n"),
+            ("suite.pharaoh.toml", "[[tests]]\nname = \"a\"\n"),
+            ("target/debug/case.toml", case),
+            ("node_modules/dep/case.toml", case),
+            (".github/case.toml", case),
+        ] {
+            fs::write(dir.path().join(file), content).unwrap();
+        }
+        let gatherer = TrycmdGatherer::new(dir.path().to_string_lossy().into_owned());
+
+        // WHEN
+        let collection = gatherer.gather().unwrap();
+
+        // THEN
+        assert_eq!(TestSuiteCollection::new(vec![]), collection);
+    }
+
+    #[test]
+    fn test_new_calls_with_dependencies() {
+        // GIVEN
+        let walker = DefaultWalker::with_patterns(PATTERNS);
+
+        // WHEN
+        let gatherer = TrycmdGatherer::new(".".to_string());
+
+        // THEN
+        assert_eq!(walker, gatherer.walker);
+    }
+}
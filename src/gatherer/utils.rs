@@ -0,0 +1,178 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path;
+use std::sync::OnceLock;
+
+pub fn get_stem(path: &path::Path, search_dir: &str) -> String {
+    get_relative(&path.with_extension(""), search_dir)
+}
+
+/// The path relative to the search directory, as is
+fn get_relative(path: &path::Path, search_dir: &str) -> String {
+    path.strip_prefix(search_dir)
+        .expect("path is not part of search dir")
+        .display()
+        .to_string()
+}
+
+/// The name of the suite of the test at `path`, and the name of the test
+///
+/// The suite is the directory of the test, or the search directory itself for top-level tests.
+/// The path is taken as is, since the dots of a directory do not start an extension: pass a file
+/// without its extension.
+pub fn get_names(path: &path::Path, search_dir: &str) -> (String, String) {
+    let name = get_relative(path, search_dir);
+    match name.rsplit_once('/') {
+        Some((suite, test)) => (suite.to_string(), test.to_string()),
+        None => {
+            let search_dir = fs::canonicalize(search_dir).ok();
+            let suite = search_dir
+                .as_deref()
+                .and_then(path::Path::file_name)
+                .map_or_else(|| ".".to_string(), |name| name.to_string_lossy().into());
+            (suite, name)
+        }
+    }
+}
+
+/// The fence and the tag of a Markdown line opening a code block, if it does
+pub fn open_fence(line: &str) -> Option<(&str, &str)> {
+    static FENCE: OnceLock<Regex> = OnceLock::new();
+    let fence = FENCE.get_or_init(|| Regex::new(r"^(```+|~~~+)\s*([^\s`]*)").unwrap());
+    let captures = fence.captures(line)?;
+    Some((captures.get(1)?.as_str(), captures.get(2)?.as_str()))
+}
+
+/// Whether a Markdown line closes the code block opened with `fence`
+pub fn closes_fence(line: &str, fence: &str) -> bool {
+    line.trim_end().starts_with(fence) && line.trim_end().trim_start_matches(&fence[..1]).is_empty()
+}
+
+/// Remove the status line, such as `[1]`, from the end of an expected output, and return the status
+pub fn take_status(stdout: &mut String) -> i32 {
+    static STATUS: OnceLock<Regex> = OnceLock::new();
+    let status = STATUS.get_or_init(|| Regex::new(r"(?:^|\n)\[(-?\d+)\]\n$").unwrap());
+    let (start, code) = match status.captures(stdout) {
+        Some(captures) => (captures.get(0).unwrap().start(), captures[1].parse().ok()),
+        None => return 0,
+    };
+    match code {
+        Some(code) => {
+            // Keep the newline ending the last line of output
+            let keep = if start == 0 { 0 } else { start + 1 };
+            stdout.truncate(keep);
+            code
+        }
+        None => 0,
+    }
+}
+
+/// Number the names that were already seen, from the second occurrence on
+pub fn number_duplicates<'a>(names: impl Iterator<Item = &'a mut String>) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        let count = seen.entry(name.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            *name = format!("{} [{}]", name, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_status() {
+        let mut failed = "a\nb\n[2]\n".to_string();
+        let mut silent = "[1]\n".to_string();
+        let mut succeeded = "[not a status]\n".to_string();
+
+        assert_eq!(2, take_status(&mut failed));
+        assert_eq!(1, take_status(&mut silent));
+        assert_eq!(0, take_status(&mut succeeded));
+        assert_eq!("a\nb\n", failed);
+        assert_eq!("", silent);
+        assert_eq!("[not a status]\n", succeeded);
+    }
+
+    #[test]
+    fn test_number_duplicates() {
+        let mut names = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "a".to_string(),
+            "a".to_string(),
+        ];
+
+        number_duplicates(names.iter_mut());
+
+        assert_eq!(vec!["a", "b", "a [2]", "a [3]"], names);
+    }
+
+    #[test]
+    fn test_get_stub_with_leading_dot() {
+        let path = path::Path::new("./foo.yaml");
+        let search_dir = ".";
+
+        assert_eq!("foo", get_stem(path, search_dir));
+    }
+
+    #[test]
+    fn test_get_stub_subdir() {
+        let path = path::Path::new("foo/bar.yaml");
+        let search_dir = "foo";
+
+        assert_eq!("bar", get_stem(path, search_dir));
+    }
+
+    #[test]
+    fn test_get_stub_subdir_trailing_slash() {
+        let path = path::Path::new("foo/bar.yaml");
+        let search_dir = "foo/";
+
+        assert_eq!("bar", get_stem(path, search_dir));
+    }
+
+    #[test]
+    fn test_get_stub_sub_yaml() {
+        let path = path::Path::new("./foo/bar.yaml");
+        let search_dir = ".";
+
+        assert_eq!("foo/bar", get_stem(path, search_dir));
+    }
+
+    #[test]
+    fn test_get_stub_sub_yaml_in_subdir() {
+        let path = path::Path::new("subdir/foo/bar.yaml");
+        let search_dir = "subdir/";
+
+        assert_eq!("foo/bar", get_stem(path, search_dir));
+    }
+
+    #[test]
+    fn test_get_names_keeps_dots() {
+        let v1 = path::Path::new("tests/cli/v1.2");
+        let v2 = path::Path::new("tests/cli/v1.3");
+
+        assert_eq!(
+            ("cli".to_string(), "v1.2".to_string()),
+            get_names(v1, "tests")
+        );
+        assert_eq!(
+            ("cli".to_string(), "v1.3".to_string()),
+            get_names(v2, "tests")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "path is not part of search dir")]
+    fn test_get_stub_not_part_of_search_dir() {
+        let path = path::Path::new("anotherdir/foo.yaml");
+        let search_dir = "mydir/";
+
+        get_stem(path, search_dir);
+    }
+}
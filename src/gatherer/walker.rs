@@ -0,0 +1,74 @@
+use anyhow::Result;
+use globwalk::GlobWalkerBuilder;
+use std::path::PathBuf;
+
+pub trait Walker {
+    fn walk(&self, search_dir: &str) -> Result<Vec<PathBuf>>;
+}
+
+/// The directories that no walker looks into: build outputs, dependencies and hidden directories
+pub(crate) const EXCLUDED: &[&str] = &["!**/target/**", "!**/node_modules/**", "!**/.*/**"];
+
+/// Walk for the files matching some glob patterns, in order, outside of the [EXCLUDED] directories
+#[derive(Eq, PartialEq, Debug)]
+pub struct DefaultWalker {
+    patterns: Vec<String>,
+}
+
+impl DefaultWalker {
+    pub fn with_patterns(patterns: &[&str]) -> Self {
+        DefaultWalker {
+            patterns: patterns
+                .iter()
+                .chain(EXCLUDED)
+                .map(|pattern| pattern.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Walker for DefaultWalker {
+    fn walk(&self, search_dir: &str) -> Result<Vec<PathBuf>> {
+        Ok(GlobWalkerBuilder::from_patterns(search_dir, &self.patterns)
+            .min_depth(1)
+            .sort_by(|a, b| a.path().cmp(b.path()))
+            .build()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.into_path())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_walk_skips_excluded_dirs() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a.t",
+            "sub/b.t",
+            "target/c.t",
+            "node_modules/d.t",
+            ".git/e.t",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        // WHEN
+        let paths = DefaultWalker::with_patterns(&["**/*.t"])
+            .walk(&dir.path().to_string_lossy())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![dir.path().join("a.t"), dir.path().join("sub/b.t")],
+            paths
+        );
+    }
+}
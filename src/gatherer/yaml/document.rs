@@ -0,0 +1,431 @@
+//! The documents of test files, in any of their formats
+//!
+//! Each document is a `suite` header or a test case, and knows where it comes from, so that its
+//! errors can name the file, the index of the document and, in YAML, the line and column.
+use anyhow::{anyhow, bail, Context, Result};
+use regex::{NoExpand, Regex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path;
+use std::process::Stdio;
+use std::sync::OnceLock;
+
+use crate::runner::executor::build_command;
+use crate::types::testcase::{Cmd, Shell};
+
+/// Split the text of a test file into its documents, each a `suite` header or a test case
+type Loader = fn(&str) -> Result<Vec<Value>>;
+
+/// The loaders of the formats of test files, by extension
+///
+/// Whatever its format, a file is converted to YAML documents, so that all formats share the same
+/// schema.
+const LOADERS: &[(&str, Loader)] = &[
+    ("yaml", load_yaml),
+    ("yml", load_yaml),
+    ("json", load_json),
+    ("toml", load_toml),
+    (GENERATOR_EXTENSION, load_generated),
+];
+
+/// The extension of the executables whose stdout is a test file
+const GENERATOR_EXTENSION: &str = "pharaoh-gen";
+
+/// The documents of a YAML file, separated by `---`
+fn load_yaml(text: &str) -> Result<Vec<Value>> {
+    serde_yaml::Deserializer::from_str(text)
+        .map(Value::deserialize)
+        .collect::<Result<Vec<Value>, _>>()
+        // The source of a syntax error repeats its message
+        .map_err(|error| anyhow!("{}", error))
+}
+
+/// The documents of a JSON file, which can be objects or arrays of objects, one after the other
+fn load_json(text: &str) -> Result<Vec<Value>> {
+    let mut documents = vec![];
+    for value in serde_json::Deserializer::from_str(text).into_iter() {
+        match value? {
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    documents.push(to_yaml(&value)?);
+                }
+            }
+            value => documents.push(to_yaml(&value)?),
+        }
+    }
+    Ok(documents)
+}
+
+/// The documents of a TOML file, which is either a single test case, or an optional `[suite]`
+/// table and `[[test]]` tables
+fn load_toml(text: &str) -> Result<Vec<Value>> {
+    let mut table: toml::value::Table = toml::from_str(text)?;
+    let mut documents = vec![];
+    if let Some(suite) = table.remove("suite") {
+        let mut header = toml::value::Table::new();
+        header.insert("suite".to_string(), suite);
+        documents.push(to_yaml(&header)?);
+    }
+    match table.remove("test") {
+        Some(toml::Value::Array(tests)) => {
+            if let Some(key) = table.keys().next() {
+                bail!("unexpected key `{}` next to the `[[test]]` tables", key);
+            }
+            for test in tests {
+                documents.push(to_yaml(&test)?);
+            }
+        }
+        Some(_) => bail!("`test` must be an array of tables, written `[[test]]`"),
+        None if table.is_empty() => {}
+        None => documents.push(to_yaml(&table)?),
+    }
+    Ok(documents)
+}
+
+/// Whether the file at `path` is a generator, which is run to get its documents
+pub fn is_generator(path: &path::Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()) == Some(GENERATOR_EXTENSION)
+}
+
+/// The documents of a test file, with the loader of its extension
+fn load_values(path: &path::Path) -> Result<Vec<Value>> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let text = match is_generator(path) {
+        true => {
+            let generator = Cmd::Argv(vec![fs::canonicalize(path)?.to_string_lossy().into_owned()]);
+            run_generator(&generator, &Shell::default(), suite_dir(Some(path)))
+                .with_context(|| format!("failed to run `{}`", path.display()))?
+        }
+        false => fs::read_to_string(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?,
+    };
+    let load = LOADERS
+        .iter()
+        .find(|(loader_extension, _)| Some(*loader_extension) == extension)
+        .map_or(load_yaml as Loader, |(_, load)| *load);
+    load(&text)
+}
+
+/// The documents printed by a generator, in JSON if they are, in YAML otherwise
+fn load_generated(text: &str) -> Result<Vec<Value>> {
+    load_json(text).or_else(|_| load_yaml(text))
+}
+
+/// Run a generator in `dir`, and return its stdout
+pub fn run_generator(cmd: &Cmd, shell: &Shell, dir: &path::Path) -> Result<String> {
+    let output = build_command(cmd, shell, env::var_os("PATH").as_deref())?
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "the generator exited with {}:\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// The directory of a test file, against which the files it references are resolved
+pub fn suite_dir(path: Option<&path::Path>) -> &path::Path {
+    path.and_then(path::Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| path::Path::new("."))
+}
+
+fn to_yaml(value: &impl Serialize) -> Result<Value> {
+    Ok(serde_yaml::to_value(value)?)
+}
+
+/// A `suite` header or a test case, as a YAML value
+#[derive(PartialEq, Debug, Clone)]
+pub struct Document {
+    pub value: Value,
+    pub origin: Origin,
+}
+
+/// Where a [Document] comes from
+#[derive(PartialEq, Debug, Clone)]
+pub struct Origin {
+    /// What produced the document, such as the name of its file
+    source: String,
+    /// The file of the document, against which its relative paths are resolved
+    file: Option<path::PathBuf>,
+    /// The YAML file of the document, in which its errors can be located
+    yaml_file: Option<path::PathBuf>,
+    /// The index of the document in its source
+    index: usize,
+}
+
+impl Origin {
+    /// The file of the document, if it was read from one rather than printed by a command
+    pub fn file(&self) -> Option<&path::Path> {
+        self.file.as_deref()
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, document {}", self.source, self.index + 1)
+    }
+}
+
+impl Document {
+    /// The documents of a test file, with the loader of its extension
+    pub fn load_file(path: &path::Path) -> Result<Vec<Document>> {
+        let source = format!("`{}`", path.display());
+        let values = load_values(path).with_context(|| source.clone())?;
+        let is_yaml = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let file = Some(path.to_path_buf());
+        let yaml_file = file.clone().filter(|_| is_yaml);
+        Ok(Self::from_values(values, source, file, yaml_file))
+    }
+
+    /// The documents of a YAML text, that does not come from a file
+    pub fn load_str(text: &str, source: &str) -> Result<Vec<Document>> {
+        let values = load_yaml(text).with_context(|| source.to_string())?;
+        Ok(Self::from_values(values, source.to_string(), None, None))
+    }
+
+    /// The documents printed by a generator, in JSON or YAML
+    pub fn load_generated(text: &str, source: &str) -> Result<Vec<Document>> {
+        let values = load_generated(text).with_context(|| source.to_string())?;
+        Ok(Self::from_values(values, source.to_string(), None, None))
+    }
+
+    fn from_values(
+        values: Vec<Value>,
+        source: String,
+        file: Option<path::PathBuf>,
+        yaml_file: Option<path::PathBuf>,
+    ) -> Vec<Document> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| Document {
+                value,
+                origin: Origin {
+                    source: source.clone(),
+                    file: file.clone(),
+                    yaml_file: yaml_file.clone(),
+                    index,
+                },
+            })
+            .collect()
+    }
+
+    /// Deserialize the document, rejecting the unknown fields with a suggestion
+    ///
+    /// If the document comes from a YAML file, the error is located in it.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_yaml::from_value(self.value.clone()).or_else(|error| {
+            let error = self.deserialize_from_file::<T>().unwrap_or(error);
+            bail!("{}", suggest(&error.to_string()))
+        })
+    }
+
+    /// The error of deserializing the document from its YAML file, if it has one and it fails there
+    ///
+    /// The document may not fail in its file, once the fields it `extends` are removed.
+    fn deserialize_from_file<T: DeserializeOwned>(&self) -> Option<serde_yaml::Error> {
+        let text = fs::read_to_string(self.origin.yaml_file.as_ref()?).ok()?;
+        // The documents before this one must be read for the parser to get to it
+        for (index, document) in serde_yaml::Deserializer::from_str(&text).enumerate() {
+            if index == self.origin.index {
+                return T::deserialize(document).err();
+            }
+            Value::deserialize(document).ok()?;
+        }
+        None
+    }
+}
+
+/// Replace the list of the expected fields of an unknown field error with the closest one
+///
+/// The other errors are left as they are.
+pub fn suggest(message: &str) -> String {
+    static UNKNOWN_FIELD: OnceLock<Regex> = OnceLock::new();
+    let unknown_field = UNKNOWN_FIELD.get_or_init(|| {
+        Regex::new(r"unknown field `([^`]*)`, (?:expected|there are no fields)((?: one of)? `[^`]*`(?:(?:,| or) `[^`]*`)*)?").unwrap()
+    });
+    let captures = match unknown_field.captures(message) {
+        Some(captures) => captures,
+        None => return message.to_string(),
+    };
+    let field = &captures[1];
+    let expected = captures.get(2).map_or("", |expected| expected.as_str());
+    let closest = expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| (strsim::levenshtein(field, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min();
+    let replacement = match closest {
+        Some((_, candidate)) => format!("unknown field `{}`, did you mean `{}`?", field, candidate),
+        None => format!("unknown field `{}`", field),
+    };
+    unknown_field
+        .replace(message, NoExpand(&replacement))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::testcase::TestCase;
+    use indoc::indoc;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_load_json() {
+        // GIVEN
+        let text = r#"{"suite": {"vars": {"x": "1"}}}
+            [{"name": "a", "cmd": "true"}, {"name": "b", "cmd": ["false"], "status": 1}]"#;
+
+        // WHEN
+        let documents = load_json(text).unwrap();
+
+        // THEN
+        assert_eq!(
+            load_yaml(indoc! {r#"
+                suite:
+                  vars:
+                    x: "1"
+                ---
+                name: a
+                cmd: "true"
+                ---
+                name: b
+                cmd: ["false"]
+                status: 1
+            "#})
+            .unwrap(),
+            documents
+        );
+    }
+
+    #[test]
+    fn test_load_toml() {
+        // GIVEN
+        let tests = indoc! {r#"
+            [suite]
+            shell = "bash"
+
+            [[test]]
+            name = "a"
+            cmd = "true"
+
+            [[test]]
+            name = "b"
+            cmd = "cat"
+            stdin = """
+            line
+            """
+        "#};
+        let single = "name = \"a\"\ncmd = \"true\"\n";
+        let mixed = "name = \"a\"\n[[test]]\nname = \"b\"\n";
+
+        // WHEN
+        let tests = load_toml(tests).unwrap();
+        let single = load_toml(single).unwrap();
+        let mixed = load_toml(mixed);
+
+        // THEN
+        assert_eq!(
+            load_yaml(indoc! {r#"
+                suite:
+                  shell: bash
+                ---
+                name: a
+                cmd: "true"
+                ---
+                name: b
+                cmd: cat
+                stdin: "line\n"
+            "#})
+            .unwrap(),
+            tests
+        );
+        assert_eq!(load_yaml("name: a\ncmd: \"true\"\n").unwrap(), single);
+        assert_eq!(
+            "unexpected key `name` next to the `[[test]]` tables",
+            mixed.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_suggest() {
+        // GIVEN
+        let typo =
+            "unknown field `stdot`, expected one of `name`, `stdout`, `stderr` at line 3 column 1";
+        let unrelated = "unknown field `flavour`, expected `name` or `cmd`";
+        let other = "invalid type: string \"zero\", expected i32";
+        let dollar = "unknown field `$stdout`, expected one of `name`, `stdout`, `$schema`";
+
+        // WHEN
+        let typo = suggest(typo);
+        let unrelated = suggest(unrelated);
+        let other = suggest(other);
+        let dollar = suggest(dollar);
+
+        // THEN
+        assert_eq!(
+            "unknown field `stdot`, did you mean `stdout`? at line 3 column 1",
+            typo
+        );
+        assert_eq!("unknown field `flavour`", unrelated);
+        assert_eq!("invalid type: string \"zero\", expected i32", other);
+        assert_eq!("unknown field `$stdout`, did you mean `stdout`?", dollar);
+    }
+
+    #[test]
+    fn test_deserialize_interact_unknown_field() {
+        // GIVEN
+        let documents = Document::load_generated(
+            "name: a\ninteract:\n  - expect: \"> \"\n    timout: 1s\n",
+            "the test",
+        )
+        .unwrap();
+
+        // WHEN
+        let result = documents[0].deserialize::<TestCase>();
+
+        // THEN
+        assert_eq!(
+            "unknown field `timout`, did you mean `timeout`?",
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_location() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tests.yaml");
+        fs::write(&path, "name: a\n---\nname: b\nstatus: zero\n").unwrap();
+
+        let documents = Document::load_file(&path).unwrap();
+
+        // WHEN
+        let result = documents[1].deserialize::<BTreeMap<String, i32>>();
+
+        // THEN
+        assert_eq!(
+            format!("`{}`, document 2", path.display()),
+            documents[1].origin.to_string()
+        );
+        assert_eq!(
+            "name: invalid type: string \"b\", expected i32 at line 3 column 7",
+            result.unwrap_err().to_string()
+        );
+    }
+}
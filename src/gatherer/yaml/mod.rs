@@ -1,18 +1,38 @@
 //! Implementation of a Gatherer for YAML files
-use anyhow::Result;
+//!
+//! Test files can also be written in JSON or TOML, with the same schema as in YAML, or be
+//! generators: executables that print test files in YAML or JSON.
+use anyhow::{bail, Result};
 use std::path;
 
 use crate::gatherer::Gatherer;
 use crate::types::testcase::{TestSuite, TestSuiteCollection};
 
-mod parser;
-mod utils;
-mod walker;
+use crate::gatherer::walker::DefaultWalker;
+use crate::gatherer::{utils, walker};
 
-use parser::DefaultParser;
-use walker::DefaultWalker;
+mod document;
+mod parser;
 
-/// Gather testcase from YAML files in a directory
+pub(crate) use document::is_generator;
+pub(crate) use parser::DefaultParser;
+
+/// The files that hold test suites, in any of the formats of the parser
+///
+/// JSON and TOML files are only test files when marked as such, since projects have plenty of
+/// others, such as `package.json` or `Cargo.toml`.
+pub(crate) const PATTERNS: &[&str] = &[
+    "**/*.yaml",
+    "**/*.yml",
+    "**/*.pharaoh.json",
+    "**/*.pharaoh.toml",
+    "**/*.pharaoh-gen",
+];
+
+/// The suffix that marks JSON and TOML test files, left out of the names of their suites
+const MARKER: &str = ".pharaoh";
+
+/// Gather testcase from YAML, JSON or TOML files in a directory
 pub struct YamlGatherer<Parser: parser::Parser, Walker: walker::Walker> {
     search_dir: String,
     parser: Parser,
@@ -22,7 +42,21 @@ pub struct YamlGatherer<Parser: parser::Parser, Walker: walker::Walker> {
 impl YamlGatherer<DefaultParser, DefaultWalker> {
     /// Constructs a new [YamlGatherer]
     pub fn new(search_dir: String) -> Self {
-        Self::with_dependencies(search_dir, DefaultParser::new(), DefaultWalker::new())
+        Self::with_dependencies(
+            search_dir,
+            DefaultParser::new(),
+            DefaultWalker::with_patterns(PATTERNS),
+        )
+    }
+
+    /// Constructs a [YamlGatherer] that runs nothing, and so leaves out the test cases of
+    /// generators
+    pub fn without_generators(search_dir: String) -> Self {
+        Self::with_dependencies(
+            search_dir,
+            DefaultParser::without_generators(),
+            DefaultWalker::with_patterns(PATTERNS),
+        )
     }
 }
 
@@ -38,9 +72,17 @@ where
             walker,
         }
     }
-    fn get_testsuite_from_path(&self, path: &path::Path) -> Result<TestSuite> {
+    /// The test suite of a file, unless it is only meant to be included by others
+    fn get_testsuite_from_path(&self, path: &path::Path) -> Result<Option<TestSuite>> {
+        if self.parser.is_include_only(path)? {
+            return Ok(None);
+        }
         let testsuite_name = utils::get_stem(path, &self.search_dir);
-        self.parser.parse_file(path, testsuite_name)
+        let testsuite_name = match testsuite_name.strip_suffix(MARKER) {
+            Some(name) => name.to_string(),
+            None => testsuite_name,
+        };
+        self.parser.parse_file(path, testsuite_name).map(Some)
     }
 }
 
@@ -49,14 +91,21 @@ where
     Parser: parser::Parser,
     Walker: walker::Walker,
 {
+    /// Gather the test suites of all the files, or report the errors of all of them
     fn gather(&self) -> Result<TestSuiteCollection> {
         let entries = self.walker.walk(&self.search_dir)?;
 
-        // FIXME: a more elegant way to leave early without collecting into a vec?
-        let testsuites: Vec<TestSuite> = entries
-            .into_iter()
-            .map(|path| self.get_testsuite_from_path(&path))
-            .collect::<Result<_>>()?;
+        let mut testsuites = vec![];
+        let mut errors = vec![];
+        for path in entries {
+            match self.get_testsuite_from_path(&path) {
+                Ok(testsuite) => testsuites.extend(testsuite),
+                Err(error) => errors.push(format!("{:#}", error)),
+            }
+        }
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
 
         Ok(TestSuiteCollection::new(testsuites))
     }
@@ -79,10 +128,20 @@ mod tests {
             Ok(TestSuite {
                 name,
                 tests: vec![],
+                services: vec![],
+                shared_shell: None,
             })
         }
     }
 
+    struct FailingParser {}
+
+    impl parser::Parser for FailingParser {
+        fn parse_file(&self, path: &path::Path, _name: String) -> Result<TestSuite> {
+            bail!("`{}` is broken", path.display())
+        }
+    }
+
     use std::path::PathBuf;
 
     struct DummyWalker {
@@ -116,18 +175,40 @@ mod tests {
             TestSuiteCollection {
                 testsuites: vec![TestSuite {
                     name: "foo".to_string(),
-                    tests: vec![]
+                    tests: vec![],
+                    services: vec![],
+                    shared_shell: None,
                 }]
             },
             collection.unwrap()
         );
     }
 
+    #[test]
+    fn test_gather_reports_all_files() {
+        // GIVEN
+        let parser = FailingParser {};
+        let walker = DummyWalker::new(vec![
+            PathBuf::from("./foo.yaml"),
+            PathBuf::from("./bar.yaml"),
+        ]);
+        let gatherer = YamlGatherer::with_dependencies(".".to_string(), parser, walker);
+
+        // WHEN
+        let collection = gatherer.gather();
+
+        // THEN
+        assert_eq!(
+            "`./foo.yaml` is broken\n`./bar.yaml` is broken",
+            collection.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_new_calls_with_dependencies() {
         // GIVEN
         let parser = DefaultParser::new();
-        let walker = DefaultWalker::new();
+        let walker = DefaultWalker::with_patterns(PATTERNS);
 
         // WHEN
         let gatherer = YamlGatherer::new(".".to_string());
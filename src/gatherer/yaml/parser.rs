@@ -1,52 +1,600 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use globwalk::GlobWalkerBuilder;
 use serde::Deserialize;
 use serde_yaml::Value;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 use std::path;
+use std::process;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::types::testcase::{TestCase, TestSuite};
+use super::document::{self, Document};
+use crate::types::testcase::{Case, Cases, Cmd, SuiteConfig, TestCase, TestSuite};
+use crate::types::vars::{self, Vars};
 
 pub trait Parser {
     fn parse_file(&self, path: &path::Path, name: String) -> Result<TestSuite>;
+
+    /// Whether the file is only meant to be included by others, and so is not a suite of its own
+    fn is_include_only(&self, _path: &path::Path) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// A document holding the settings of a suite
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    suite: SuiteConfig,
+}
+
+/// Merge into a test case that `extends` a template or another test case the fields of the
+/// latter, which it overrides
+///
+/// The name of the template or test case is not inherited.
+fn resolve_extends(
+    document: &Document,
+    documents: &[Document],
+    templates: &BTreeMap<String, Value>,
+) -> Result<Document> {
+    let name = document.value.get("name").and_then(Value::as_str);
+    let mut chain = name.map(str::to_string).into_iter().collect();
+    Ok(Document {
+        value: extend(&document.value, documents, templates, &mut chain)?,
+        origin: document.origin.clone(),
+    })
+}
+
+/// Merge into `document` the fields of the template or test case it `extends`, if any
+///
+/// `chain` holds the names of the documents being extended, to detect cycles.
+fn extend(
+    document: &Value,
+    documents: &[Document],
+    templates: &BTreeMap<String, Value>,
+    chain: &mut Vec<String>,
+) -> Result<Value> {
+    let parent = match document.get("extends") {
+        Some(parent) => parent
+            .as_str()
+            .context("`extends` must be the name of a template or test case")?,
+        None => return Ok(document.clone()),
+    };
+    if chain.iter().any(|name| name == parent) {
+        bail!("`extends` cycle: {} -> {}", chain.join(" -> "), parent);
+    }
+    let base = templates
+        .get(parent)
+        .or_else(|| {
+            documents
+                .iter()
+                .map(|document| &document.value)
+                .find(|document| document.get("name").and_then(Value::as_str) == Some(parent))
+        })
+        .with_context(|| {
+            format!(
+                "`extends` names an unknown template or test case `{}`",
+                parent
+            )
+        })?;
+
+    chain.push(parent.to_string());
+    let base = extend(base, documents, templates, chain)?;
+    chain.pop();
+    let (mut merged, own) = match (base, document) {
+        (Value::Mapping(base), Value::Mapping(own)) => (base, own),
+        _ => bail!(
+            "`{}` and the test cases extending it must be mappings",
+            parent
+        ),
+    };
+    merged.remove(&Value::from("name"));
+    for (key, value) in own {
+        merged.insert(key.clone(), value.clone());
+    }
+    Ok(Value::Mapping(merged))
 }
 
 #[derive(Eq, PartialEq, Debug, Default)]
-pub struct DefaultParser {}
+pub struct DefaultParser {
+    /// Whether generators are skipped rather than run, so that parsing runs nothing
+    skip_generators: bool,
+}
 
 impl DefaultParser {
     pub fn new() -> Self {
-        DefaultParser {}
+        DefaultParser {
+            skip_generators: false,
+        }
+    }
+
+    /// A parser that leaves out the test cases of generator files and `generate` commands,
+    /// rather than running them
+    pub fn without_generators() -> Self {
+        DefaultParser {
+            skip_generators: true,
+        }
+    }
+
+    /// The `suite` settings of a test file, which are the default ones for a generator
+    pub fn read_config(&self, path: &path::Path) -> Result<SuiteConfig> {
+        if document::is_generator(path) {
+            return Ok(SuiteConfig::default());
+        }
+        Ok(self.split_header(Document::load_file(path)?)?.0)
     }
 
     pub fn parse_reader(&self, reader: &mut impl std::io::Read, name: String) -> Result<TestSuite> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        self.parse(Document::load_str(&text, "the input")?, name, None)
+    }
+
+    fn parse(
+        &self,
+        documents: Vec<Document>,
+        name: String,
+        path: Option<&path::Path>,
+    ) -> Result<TestSuite> {
+        let (config, mut documents) = self.split_header(documents)?;
+        let vars = self.suite_vars(&config, path);
+
+        if let Some(generate) = config.generate.as_ref().filter(|_| !self.skip_generators) {
+            let shell = config.shell.clone().unwrap_or_default();
+            let text = document::run_generator(generate, &shell, document::suite_dir(path))
+                .context("failed to run the `generate` command of the suite")?;
+            for document in Document::load_generated(&text, "the `generate` command")? {
+                if document.value.get("suite").is_some() {
+                    bail!("the `generate` command of the suite cannot print a `suite` document");
+                }
+                documents.push(document);
+            }
+        }
+
+        let mut templates = config.templates.clone();
+        let mut chain = match path {
+            Some(path) => vec![fs::canonicalize(path)?],
+            None => vec![],
+        };
+        let mut included = vec![];
+        self.include(
+            &config.include,
+            path,
+            &mut chain,
+            &mut included,
+            &mut templates,
+        )?;
+        included.extend(documents);
+
+        // Report the errors of all the test cases at once, rather than only the first one
+        let mut tests = vec![];
+        let mut errors = vec![];
+        for document in &included {
+            // The relative paths of an included document are relative to its own file
+            let (path, vars) = match document.origin.file() {
+                Some(file) if Some(file) != path => {
+                    (Some(file), Cow::Owned(self.suite_vars(&config, Some(file))))
+                }
+                _ => (path, Cow::Borrowed(&vars)),
+            };
+            let test_cases = resolve_extends(document, &included, &templates)
+                .and_then(|document| self.expand(&document, &config, &vars, path))
+                .with_context(|| document.origin.to_string());
+            match test_cases {
+                Ok(test_cases) => tests.extend(test_cases.into_iter().map(|test_case| TestCase {
+                    name: format!("{}::{}", name, test_case.name),
+                    ..test_case
+                })),
+                Err(error) => errors.push(format!("{:#}", error)),
+            }
+        }
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+
         Ok(TestSuite {
-            tests: serde_yaml::Deserializer::from_reader(reader)
-                .map(|doc| {
-                    let value = Value::deserialize(doc)?;
-                    let mut test_case: TestCase = serde_yaml::from_value(value)?;
-                    test_case.name = format!("{}::{}", name, test_case.name);
-                    Ok(test_case)
-                })
-                .collect::<Result<Vec<TestCase>>>()?,
+            tests,
             name,
+            services: config.services,
+            shared_shell: None,
         })
     }
+
+    /// The test cases of a document, once its `for_each`, `cases` and `matrix` are expanded and
+    /// its variables resolved
+    fn expand(
+        &self,
+        document: &Document,
+        config: &SuiteConfig,
+        vars: &Vars,
+        path: Option<&path::Path>,
+    ) -> Result<Vec<TestCase>> {
+        let mut test_case: TestCase = document.deserialize()?;
+        self.apply_config(&mut test_case, config)?;
+        let mut tests = vec![];
+        for (test_case, file_vars) in self.expand_for_each(test_case, path)? {
+            for test_case in self.expand_cases(test_case, path)? {
+                for (mut test_case, combination) in self.expand_matrix(test_case) {
+                    let mut vars = vars.clone();
+                    vars.insert("test_name".to_string(), test_case.name.clone());
+                    vars.extend(file_vars.clone());
+                    vars.extend(combination);
+                    test_case.map_text(|text| vars::resolve(text, &vars));
+                    tests.push(test_case);
+                }
+            }
+        }
+        Ok(tests)
+    }
+
+    /// Separate the `suite` document of a file, if any, from its test cases
+    fn split_header(&self, documents: Vec<Document>) -> Result<(SuiteConfig, Vec<Document>)> {
+        let (headers, documents): (Vec<Document>, Vec<Document>) = documents
+            .into_iter()
+            .partition(|document| document.value.get("suite").is_some());
+        let config = match headers.as_slice() {
+            [] => SuiteConfig::default(),
+            [header] => {
+                let header: Header = header
+                    .deserialize()
+                    .with_context(|| header.origin.to_string())?;
+                header.suite
+            }
+            [_, header, ..] => bail!(
+                "{}: only one `suite` document is allowed per file",
+                header.origin
+            ),
+        };
+        Ok((config, documents))
+    }
+
+    /// Pull in the test cases and templates of the files of `include`, after the ones of their own
+    /// includes
+    ///
+    /// `chain` holds the files being included, from the top-level one, to detect cycles. The
+    /// templates already known take precedence over the included ones.
+    fn include(
+        &self,
+        include: &[String],
+        path: Option<&path::Path>,
+        chain: &mut Vec<path::PathBuf>,
+        documents: &mut Vec<Document>,
+        templates: &mut BTreeMap<String, Value>,
+    ) -> Result<()> {
+        for file in include {
+            let file = document::suite_dir(path).join(file);
+            let canonical = fs::canonicalize(&file)
+                .with_context(|| format!("failed to include `{}`", file.display()))?;
+            if let Some(start) = chain.iter().position(|included| *included == canonical) {
+                let cycle = chain[start..]
+                    .iter()
+                    .chain(std::iter::once(&canonical))
+                    .map(|included| format!("`{}`", included.display()))
+                    .collect::<Vec<_>>();
+                bail!("include cycle: {}", cycle.join(" -> "));
+            }
+
+            if self.skip_generators && document::is_generator(&file) {
+                continue;
+            }
+            let (config, included) = self.split_header(Document::load_file(&file)?)?;
+            chain.push(canonical);
+            self.include(&config.include, Some(&file), chain, documents, templates)?;
+            chain.pop();
+            for (id, template) in config.templates {
+                templates.entry(id).or_insert(template);
+            }
+            documents.extend(included);
+        }
+        Ok(())
+    }
+
+    /// The variables known when parsing, from the least to the most specific: the environment,
+    /// the built-ins, then the `vars` of the suite
+    ///
+    /// The environment variables are named `env.NAME`, so that a plain `${NAME}` is left for the
+    /// shell to expand, with the `env` and `workdir` of the test.
+    fn suite_vars(&self, config: &SuiteConfig, path: Option<&path::Path>) -> Vars {
+        let mut vars: Vars = env::vars()
+            .map(|(name, value)| (format!("env.{}", name), value))
+            .collect();
+        vars.insert(
+            "temp_dir".to_string(),
+            env::temp_dir().to_string_lossy().into_owned(),
+        );
+        vars.insert("run_id".to_string(), run_id().to_string());
+        if let Some(path) = path {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            let dir = path.parent().unwrap_or(&path);
+            vars.insert("suite_dir".to_string(), dir.to_string_lossy().into_owned());
+            vars.insert(
+                "suite_file".to_string(),
+                path.to_string_lossy().into_owned(),
+            );
+        }
+        for (name, value) in &config.vars {
+            let value = vars::resolve(value, &vars);
+            vars.insert(name.clone(), value);
+        }
+        vars
+    }
+
+    fn apply_config(&self, test_case: &mut TestCase, config: &SuiteConfig) -> Result<()> {
+        let name = format!("test case `{}`", test_case.name);
+        if test_case.steps.is_empty() {
+            self.check_command(&test_case.cmd, &test_case.script, &name)?;
+        } else if test_case.cmd != Cmd::default() || test_case.script.is_some() {
+            bail!("{} has both `steps` and a `cmd` or a `script`", name)
+        } else if let Some(field) = single_command_field(test_case) {
+            bail!(
+                "{} has both `steps` and `{}`, which only applies to a single command",
+                name,
+                field
+            )
+        }
+        for (index, step) in test_case.steps.iter().enumerate() {
+            let name = format!("step {} of {}", index + 1, name);
+            self.check_command(&step.cmd, &step.script, &name)?;
+        }
+        if test_case.shell.is_none() {
+            test_case.shell = config.shell.clone();
+        }
+        test_case.limits = test_case.limits.or(&config.limits);
+        test_case.mock_http = match (test_case.mock_http.take(), &config.mock_http) {
+            (Some(mock_http), Some(defaults)) => Some(mock_http.or(defaults)),
+            (mock_http, defaults) => mock_http.or_else(|| defaults.clone()),
+        };
+        Ok(())
+    }
+
+    /// Make a test case for each input file of the `for_each` of `test_case`, if it has one
+    ///
+    /// Each test case is fed with its input file and expects the content of its expected output
+    /// file. The `file` and `stem` variables of the input file are returned along with it.
+    fn expand_for_each(
+        &self,
+        mut test_case: TestCase,
+        path: Option<&path::Path>,
+    ) -> Result<Vec<(TestCase, Vars)>> {
+        let for_each = match test_case.for_each.take() {
+            Some(for_each) => for_each,
+            None => return Ok(vec![(test_case, Vars::new())]),
+        };
+        let dir = match path.and_then(path::Path::parent) {
+            Some(dir) => dir.to_path_buf(),
+            None => path::PathBuf::from("."),
+        };
+        let inputs = GlobWalkerBuilder::from_patterns(&dir, &[&for_each.files])
+            .sort_by(|a, b| a.path().cmp(b.path()))
+            .build()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect::<Vec<_>>();
+        if inputs.is_empty() {
+            bail!(
+                "`for_each` of test case `{}` matches no file: `{}`",
+                test_case.name,
+                for_each.files
+            );
+        }
+
+        inputs
+            .into_iter()
+            .map(|input| {
+                let relative = input.strip_prefix(&dir).unwrap_or(&input);
+                let file = fs::canonicalize(&input)?;
+                let file_vars = Vars::from([
+                    ("file".to_string(), file.to_string_lossy().into_owned()),
+                    (
+                        "stem".to_string(),
+                        input
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned(),
+                    ),
+                ]);
+                let output = file
+                    .parent()
+                    .unwrap_or(&file)
+                    .join(vars::substitute(&for_each.stdout, &file_vars));
+                if !output.is_file() {
+                    bail!(
+                        "test case `{}` has no expected output `{}` for `{}`",
+                        test_case.name,
+                        output.display(),
+                        relative.display()
+                    );
+                }
+                let test_case = TestCase {
+                    name: format!("{} [{}]", test_case.name, relative.display()),
+                    stdin: vars::escape(
+                        &fs::read_to_string(&input)
+                            .with_context(|| format!("failed to read `{}`", input.display()))?,
+                    ),
+                    stdout: vars::escape(
+                        &fs::read_to_string(&output)
+                            .with_context(|| format!("failed to read `{}`", output.display()))?,
+                    ),
+                    ..test_case.clone()
+                };
+                Ok((test_case, file_vars))
+            })
+            .collect()
+    }
+
+    /// Make a test case for each row of the `cases` of `test_case`, if it has some
+    fn expand_cases(
+        &self,
+        mut test_case: TestCase,
+        path: Option<&path::Path>,
+    ) -> Result<Vec<TestCase>> {
+        let rows = match test_case.cases.take() {
+            Some(Cases::Rows(rows)) => rows,
+            Some(Cases::File(file)) => self.read_cases(&file, path).with_context(|| {
+                format!(
+                    "failed to read the cases of test case `{}` from `{}`",
+                    test_case.name, file
+                )
+            })?,
+            None => return Ok(vec![test_case]),
+        };
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let row_name = row.name.unwrap_or_else(|| format!("row {}", index + 1));
+                TestCase {
+                    name: format!("{} [{}]", test_case.name, row_name),
+                    stdin: row.stdin.unwrap_or_else(|| test_case.stdin.clone()),
+                    stdout: row.stdout.unwrap_or_else(|| test_case.stdout.clone()),
+                    stderr: row.stderr.unwrap_or_else(|| test_case.stderr.clone()),
+                    status: row.status.unwrap_or(test_case.status),
+                    ..test_case.clone()
+                }
+            })
+            .collect())
+    }
+
+    /// Read the rows of a CSV or TSV file, relative to the file of the suite
+    ///
+    /// A cell is a single line, so a newline is added to the non-empty inputs and outputs that do
+    /// not end with one.
+    fn read_cases(&self, file: &str, path: Option<&path::Path>) -> Result<Vec<Case>> {
+        let file = match path.and_then(path::Path::parent) {
+            Some(dir) => dir.join(file),
+            None => path::PathBuf::from(file),
+        };
+        let delimiter = match file.extension() {
+            Some(extension) if extension == "tsv" => b'\t',
+            _ => b',',
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_path(&file)?;
+        // The cells are taken as is, with a trailing newline, rather than as variable references
+        let cell = |text: Option<String>| {
+            text.map(|text| match text.is_empty() || text.ends_with('\n') {
+                true => vars::escape(&text),
+                false => vars::escape(&text) + "\n",
+            })
+        };
+        reader
+            .deserialize()
+            .map(|row| {
+                let row: Case = row?;
+                Ok(Case {
+                    stdin: cell(row.stdin),
+                    stdout: cell(row.stdout),
+                    stderr: cell(row.stderr),
+                    ..row
+                })
+            })
+            .collect()
+    }
+
+    /// Make a test case for each combination of the `matrix` of `test_case`, if it has one
+    ///
+    /// The combination is appended to the name of each test case, and returned along with it.
+    fn expand_matrix(&self, mut test_case: TestCase) -> Vec<(TestCase, Vec<(String, String)>)> {
+        let matrix = match test_case.matrix.take() {
+            Some(matrix) => matrix,
+            None => return vec![(test_case, vec![])],
+        };
+        matrix
+            .combinations()
+            .into_iter()
+            .map(|combination| {
+                let suffix = combination
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let test_case = TestCase {
+                    name: format!("{} [{}]", test_case.name, suffix),
+                    ..test_case.clone()
+                };
+                (test_case, combination)
+            })
+            .collect()
+    }
+
+    /// Check that exactly one of `cmd` and `script` is given
+    fn check_command(&self, cmd: &Cmd, script: &Option<String>, name: &str) -> Result<()> {
+        match (cmd, script) {
+            (cmd, None) if *cmd == Cmd::default() => {
+                bail!("{} needs a `cmd` or a `script`", name)
+            }
+            (cmd, Some(_)) if *cmd != Cmd::default() => {
+                bail!("{} has both a `cmd` and a `script`", name)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An identifier of the current run of Pharaoh, the same for all suites
+fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(|| {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        format!("{}-{}", started.as_secs(), process::id())
+    })
 }
 
 impl Parser for DefaultParser {
     fn parse_file(&self, path: &path::Path, name: String) -> Result<TestSuite> {
-        let mut file = fs::File::open(path)?;
+        if self.skip_generators && document::is_generator(path) {
+            return self.parse(vec![], name, Some(path));
+        }
+        self.parse(Document::load_file(path)?, name, Some(path))
+    }
 
-        self.parse_reader(&mut file, name)
+    fn is_include_only(&self, path: &path::Path) -> Result<bool> {
+        Ok(self.read_config(path)?.include_only)
     }
 }
 
+/// The first field of `test_case` that is set, but that its `steps` would not honour
+fn single_command_field(test_case: &TestCase) -> Option<&'static str> {
+    let fields = [
+        ("stdin", !test_case.stdin.is_empty()),
+        ("stdout", !test_case.stdout.is_empty()),
+        ("stderr", !test_case.stderr.is_empty()),
+        ("status", test_case.status != 0),
+        ("interact", !test_case.interact.is_empty()),
+        ("signals", !test_case.signals.is_empty()),
+        ("terminate_within", test_case.terminate_within.is_some()),
+        ("max_duration", test_case.max_duration.is_some()),
+        ("max_memory", test_case.max_memory.is_some()),
+        ("mock_http", test_case.mock_http.is_some()),
+        ("expect_calls", test_case.expect_calls.is_some()),
+        ("capture", !test_case.capture.is_empty()),
+    ];
+    fields.iter().find(|(_, set)| *set).map(|(field, _)| *field)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use indoc::indoc;
     use std::io::Cursor;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    use crate::types::signal::Signal;
+    use crate::types::testcase::{
+        ExpectedCall, ExpectedRequest, InteractStep, Limits, MockHttp, Readiness, Route, SendEof,
+        Service, Shell, Step, Stub,
+    };
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_from_reader() {
@@ -74,12 +622,15 @@ mod tests {
                 name: "mytestsuite".to_string(),
                 tests: vec![TestCase {
                     name: "mytestsuite::cat should work".to_string(),
-                    cmd: "cat".to_string(),
+                    cmd: "cat".into(),
                     stdin: "this is a line\n".to_string(),
                     stdout: "this is a line\n".to_string(),
                     stderr: "".to_string(),
-                    status: 0
-                }]
+                    status: 0,
+                    ..Default::default()
+                }],
+                services: vec![],
+                shared_shell: None,
             },
             result
         );
@@ -109,26 +660,1137 @@ mod tests {
                 tests: vec![
                     TestCase {
                         name: "mytestsuite::a first test".to_string(),
-                        cmd: "echo".to_string(),
+                        cmd: "echo".into(),
                         stdin: "".to_string(),
                         stdout: "".to_string(),
                         stderr: "".to_string(),
-                        status: 0
+                        status: 0,
+                        ..Default::default()
                     },
                     TestCase {
                         name: "mytestsuite::a second test".to_string(),
-                        cmd: "printf".to_string(),
+                        cmd: "printf".into(),
                         stdin: "".to_string(),
                         stdout: "".to_string(),
                         stderr: "".to_string(),
-                        status: 0
+                        status: 0,
+                        ..Default::default()
                     },
-                ]
+                ],
+                services: vec![],
+                shared_shell: None,
             },
             result
         );
     }
 
+    #[test]
+    fn test_from_reader_argv() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: no shell involved
+            cmd: [ls, "my file.txt"]
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            Cmd::Argv(vec!["ls".to_string(), "my file.txt".to_string()]),
+            result.tests[0].cmd
+        );
+    }
+
+    #[test]
+    fn test_from_reader_suite_shell() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              shell: bash -euo pipefail -c
+            ---
+            name: uses the suite shell
+            cmd: false | true
+            ---
+            name: uses its own shell
+            shell: [python3, -c]
+            script: |
+              print("hello")
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(2, result.tests.len());
+        assert_eq!(
+            Some(Shell(vec![
+                "bash".to_string(),
+                "-euo".to_string(),
+                "pipefail".to_string(),
+                "-c".to_string()
+            ])),
+            result.tests[0].shell
+        );
+        assert_eq!(
+            Some(Shell(vec!["python3".to_string(), "-c".to_string()])),
+            result.tests[1].shell
+        );
+        assert_eq!(
+            Some("print(\"hello\")\n".to_string()),
+            result.tests[1].script
+        );
+    }
+
+    #[test]
+    fn test_from_reader_suite_limits() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              limits:
+                cpu: 2
+                memory: 512M
+            ---
+            name: a greedy test
+            cmd: ./greedy
+            limits:
+              memory: 1G
+              processes: 10
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            Limits {
+                cpu: Some(Duration::from_secs(2)),
+                memory: Some(1 << 30),
+                processes: Some(10),
+                ..Default::default()
+            },
+            result.tests[0].limits
+        );
+    }
+
+    #[test]
+    fn test_from_reader_suite_services() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              services:
+                - name: api
+                  cmd: ./server --port $PORT
+                  ready:
+                    port: true
+                    stdout: listening
+                  timeout: 2s
+            ---
+            name: a client test
+            cmd: ./client
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![Service {
+                name: "api".to_string(),
+                cmd: "./server --port $PORT".into(),
+                shell: None,
+                ready: Readiness {
+                    port: true,
+                    stdout: Some("listening".to_string()),
+                    file: None,
+                },
+                timeout: Duration::from_secs(2),
+            }],
+            result.services
+        );
+    }
+
+    #[test]
+    fn test_from_reader_mock_http() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              mock_http:
+                routes:
+                  - path: /health
+                    body: ok
+            ---
+            name: creates a user
+            cmd: ./client create alice
+            mock_http:
+              routes:
+                - method: POST
+                  path: /users
+                  status: 201
+                  headers:
+                    Content-Type: application/json
+                  body: '{"id": 1}'
+              expect_requests:
+                - method: POST
+                  path: /users
+                  body: '{"name": "alice"}'
+            ---
+            name: checks the health
+            cmd: ./client health
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        let health = Route {
+            method: "GET".to_string(),
+            path: "/health".to_string(),
+            status: 200,
+            headers: BTreeMap::new(),
+            body: "ok".to_string(),
+        };
+        assert_eq!(
+            Some(MockHttp {
+                routes: vec![
+                    Route {
+                        method: "POST".to_string(),
+                        path: "/users".to_string(),
+                        status: 201,
+                        headers: [("Content-Type".to_string(), "application/json".to_string())]
+                            .into(),
+                        body: "{\"id\": 1}".to_string(),
+                    },
+                    health.clone(),
+                ],
+                expect_requests: Some(vec![ExpectedRequest {
+                    method: "POST".to_string(),
+                    path: "/users".to_string(),
+                    body: Some("{\"name\": \"alice\"}".to_string()),
+                }]),
+            }),
+            result.tests[0].mock_http
+        );
+        assert_eq!(
+            Some(MockHttp {
+                routes: vec![health],
+                expect_requests: None,
+            }),
+            result.tests[1].mock_http
+        );
+    }
+
+    #[test]
+    fn test_from_reader_stubs() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: restarts the service
+            cmd: ./restart myapp
+            stubs:
+              systemctl:
+                stdout: done
+                status: 3
+            expect_calls:
+              - argv: [systemctl, restart, myapp]
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            BTreeMap::from([(
+                "systemctl".to_string(),
+                Stub {
+                    stdout: "done".to_string(),
+                    stderr: "".to_string(),
+                    status: 3,
+                }
+            )]),
+            result.tests[0].stubs
+        );
+        assert_eq!(
+            Some(vec![ExpectedCall {
+                argv: vec![
+                    "systemctl".to_string(),
+                    "restart".to_string(),
+                    "myapp".to_string()
+                ],
+                stdin: None,
+            }]),
+            result.tests[0].expect_calls
+        );
+    }
+
+    #[test]
+    fn test_from_reader_cmd_or_script() {
+        // GIVEN
+        let mut neither = Cursor::new("name: nothing to run\n");
+        let mut both = Cursor::new("name: too much\ncmd: echo\nscript: echo\n");
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let neither = parser.parse_reader(&mut neither, "testsuite".to_string());
+        let both = parser.parse_reader(&mut both, "testsuite".to_string());
+
+        // THEN
+        assert_eq!(
+            "the input, document 1: test case `nothing to run` needs a `cmd` or a `script`",
+            neither.unwrap_err().to_string()
+        );
+        assert_eq!(
+            "the input, document 1: test case `too much` has both a `cmd` and a `script`",
+            both.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_from_reader_steps() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: a git workflow
+            steps:
+              - cmd: git init -q
+              - name: commit
+                script: |
+                  git add .
+                  git commit -qm first
+              - cmd: git log --format=%s
+                stdout: |
+                  first
+        "#});
+        let mut both = Cursor::new("name: too much\ncmd: echo\nsteps:\n  - cmd: echo\n");
+        let mut empty_step = Cursor::new("name: empty\nsteps:\n  - stdin: nothing\n");
+        let mut ignored = Cursor::new(indoc! {r#"
+            name: ignored
+            steps:
+              - cmd: ./install
+            expect_calls:
+              - argv: [systemctl, restart, app]
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+        let both = parser.parse_reader(&mut both, "mytestsuite".to_string());
+        let empty_step = parser.parse_reader(&mut empty_step, "mytestsuite".to_string());
+        let ignored = parser.parse_reader(&mut ignored, "mytestsuite".to_string());
+
+        // THEN
+        assert_eq!(
+            vec![
+                Step {
+                    cmd: "git init -q".into(),
+                    ..Default::default()
+                },
+                Step {
+                    name: Some("commit".to_string()),
+                    script: Some("git add .\ngit commit -qm first\n".to_string()),
+                    ..Default::default()
+                },
+                Step {
+                    cmd: "git log --format=%s".into(),
+                    stdout: "first\n".to_string(),
+                    ..Default::default()
+                },
+            ],
+            result.tests[0].steps
+        );
+        assert_eq!(
+            "the input, document 1: test case `too much` has both `steps` and a `cmd` or a `script`",
+            both.unwrap_err().to_string()
+        );
+        assert_eq!(
+            "the input, document 1: step 1 of test case `empty` needs a `cmd` or a `script`",
+            empty_step.unwrap_err().to_string()
+        );
+        assert_eq!(
+            "the input, document 1: test case `ignored` has both `steps` and `expect_calls`, \
+             which only applies to a single command",
+            ignored.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_from_reader_vars() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              vars:
+                greeting: hello
+                cache: ${temp_dir}/cache
+            ---
+            name: greets
+            cmd: echo "${greeting} from ${test_name}" > ${cache}
+            env:
+              SEARCH: ${env.PATH}
+              ORIGINAL_HOME: ${HOME}
+            stdout: |
+              $${greeting} ${unknown}
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        let test_case = &result.tests[0];
+        assert_eq!(
+            Cmd::from(format!(
+                "echo \"hello from greets\" > {}/cache",
+                env::temp_dir().display()
+            )),
+            test_case.cmd
+        );
+        assert_eq!(env::var("PATH").unwrap(), test_case.env["SEARCH"]);
+        assert_eq!("${HOME}", test_case.env["ORIGINAL_HOME"]);
+        assert_eq!("$${greeting} ${unknown}\n", test_case.stdout);
+    }
+
+    #[test]
+    fn test_from_reader_matrix() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: sort
+            cmd: ./mysort --order ${order} --algo ${algo}
+            stdin: ${order}
+            stdout: ${test_name}
+            matrix:
+              order: [asc, desc]
+              algo: [merge, quick]
+              exclude:
+                - order: desc
+                  algo: quick
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                "mytestsuite::sort [order=asc, algo=merge]",
+                "mytestsuite::sort [order=asc, algo=quick]",
+                "mytestsuite::sort [order=desc, algo=merge]",
+            ],
+            result
+                .tests
+                .iter()
+                .map(|test_case| test_case.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        let test_case = &result.tests[2];
+        assert_eq!(
+            Cmd::from("./mysort --order desc --algo merge"),
+            test_case.cmd
+        );
+        assert_eq!("desc", test_case.stdin);
+        assert_eq!("sort [order=desc, algo=merge]", test_case.stdout);
+        assert_eq!(None, test_case.matrix);
+    }
+
+    #[test]
+    fn test_from_reader_cases() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: calc
+            cmd: ./mycalc
+            stdout: "0\n"
+            cases:
+              - name: addition
+                stdin: "2 + 4\n"
+                stdout: "6\n"
+              - stdin: "2 +\n"
+                stderr: "syntax error\n"
+                status: 1
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "mytestsuite::calc [addition]".to_string(),
+                    cmd: "./mycalc".into(),
+                    stdin: "2 + 4\n".to_string(),
+                    stdout: "6\n".to_string(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "mytestsuite::calc [row 2]".to_string(),
+                    cmd: "./mycalc".into(),
+                    stdin: "2 +\n".to_string(),
+                    stdout: "0\n".to_string(),
+                    stderr: "syntax error\n".to_string(),
+                    status: 1,
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_parse_file_cases_file() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(&path, "name: calc\ncmd: ./mycalc\ncases: cases.tsv\n").unwrap();
+        fs::write(
+            dir.path().join("cases.tsv"),
+            "name\tstdin\tstdout\tstatus\naddition\t2 + 4\t6\t\nerror\t2 +\t\t1\nliteral\t${x}\t${x}\t\n",
+        )
+        .unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "suite::calc [addition]".to_string(),
+                    cmd: "./mycalc".into(),
+                    stdin: "2 + 4\n".to_string(),
+                    stdout: "6\n".to_string(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "suite::calc [error]".to_string(),
+                    cmd: "./mycalc".into(),
+                    stdin: "2 +\n".to_string(),
+                    status: 1,
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "suite::calc [literal]".to_string(),
+                    cmd: "./mycalc".into(),
+                    stdin: "$${x}\n".to_string(),
+                    stdout: "$${x}\n".to_string(),
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_parse_file_by_extension() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("suite.json");
+        let toml = dir.path().join("suite.toml");
+        fs::write(&json, r#"[{"name": "a", "cmd": "echo ${test_name}"}]"#).unwrap();
+        fs::write(
+            &toml,
+            "[[test]]\nname = \"a\"\ncmd = \"echo ${test_name}\"\n",
+        )
+        .unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let json = parser.parse_file(&json, "suite".to_string()).unwrap();
+        let toml = parser.parse_file(&toml, "suite".to_string()).unwrap();
+
+        // THEN
+        let expected = vec![TestCase {
+            name: "suite::a".to_string(),
+            cmd: "echo a".into(),
+            ..Default::default()
+        }];
+        assert_eq!(expected, json.tests);
+        assert_eq!(expected, toml.tests);
+    }
+
+    #[test]
+    fn test_parse_file_generator() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flags.pharaoh-gen");
+        fs::write(
+            &path,
+            indoc! {r#"
+                #!/bin/sh
+                for flag in -a -b; do
+                    printf '{"name": "flag %s", "cmd": "mycmd %s"}\n' "$flag" "$flag"
+                done
+            "#},
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "flags".to_string()).unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "flags::flag -a".to_string(),
+                    cmd: "mycmd -a".into(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "flags::flag -b".to_string(),
+                    cmd: "mycmd -b".into(),
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_parse_file_generate() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(
+            &path,
+            indoc! {r#"
+                suite:
+                  generate: |
+                    for file in *.in; do
+                        printf -- '---\nname: %s\ncmd: "true"\n' "$file"
+                    done
+                ---
+                name: written
+                cmd: "true"
+            "#},
+        )
+        .unwrap();
+        fs::write(dir.path().join("generated.in"), "").unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        let names = result
+            .tests
+            .iter()
+            .map(|test| &test.name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["suite::written", "suite::generated.in"], names);
+    }
+
+    #[test]
+    fn test_parse_file_without_generators() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let ran = dir.path().join("RAN");
+        let generator = dir.path().join("flags.pharaoh-gen");
+        fs::write(&generator, format!("#!/bin/sh\ntouch {}\n", ran.display())).unwrap();
+        fs::set_permissions(&generator, fs::Permissions::from_mode(0o755)).unwrap();
+        let suite = dir.path().join("suite.yaml");
+        fs::write(
+            &suite,
+            format!(
+                "suite:\n  generate: touch {}\n---\nname: written\ncmd: \"true\"\n",
+                ran.display()
+            ),
+        )
+        .unwrap();
+
+        let parser = DefaultParser::without_generators();
+
+        // WHEN
+        let generated = parser.parse_file(&generator, "flags".to_string()).unwrap();
+        let written = parser.parse_file(&suite, "suite".to_string()).unwrap();
+        let config = parser.read_config(&suite).unwrap();
+
+        // THEN
+        assert_eq!(Vec::<TestCase>::new(), generated.tests);
+        let names = written
+            .tests
+            .iter()
+            .map(|test| &test.name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["suite::written"], names);
+        assert!(config.generate.is_some());
+        assert!(!ran.exists());
+    }
+
+    #[test]
+    fn test_parse_file_generator_fails() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(&path, "suite:\n  generate: echo oops >&2; exit 3\n").unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string());
+
+        // THEN
+        assert_eq!(
+            "failed to run the `generate` command of the suite: \
+             the generator exited with exit status: 3:\noops\n",
+            format!("{:#}", result.unwrap_err())
+        );
+    }
+
+    #[test]
+    fn test_from_reader_extends() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            suite:
+              templates:
+                help:
+                  cmd: ./mycmd --help
+                  stdout: "Usage: mycmd\n"
+            ---
+            name: help works
+            extends: help
+            ---
+            name: help fails on a bad flag
+            extends: help works
+            cmd: ./mycmd --help --bad
+            status: 2
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_reader(&mut doc, "suite".to_string()).unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "suite::help works".to_string(),
+                    extends: Some("help".to_string()),
+                    cmd: "./mycmd --help".into(),
+                    stdout: "Usage: mycmd\n".to_string(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "suite::help fails on a bad flag".to_string(),
+                    extends: Some("help works".to_string()),
+                    cmd: "./mycmd --help --bad".into(),
+                    stdout: "Usage: mycmd\n".to_string(),
+                    status: 2,
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_from_reader_extends_errors() {
+        // GIVEN
+        let cycle = indoc! {r#"
+            name: a
+            extends: b
+            ---
+            name: b
+            extends: a
+        "#};
+        let unknown = "name: a\nextends: nothing\n";
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let cycle = parser.parse_reader(&mut Cursor::new(cycle), "suite".to_string());
+        let unknown = parser.parse_reader(&mut Cursor::new(unknown), "suite".to_string());
+
+        // THEN
+        assert_eq!(
+            "the input, document 1: `extends` cycle: a -> b -> a\n\
+             the input, document 2: `extends` cycle: b -> a -> b",
+            cycle.unwrap_err().to_string()
+        );
+        assert_eq!(
+            "the input, document 1: `extends` names an unknown template or test case `nothing`",
+            unknown.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_file_include() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(
+            &path,
+            indoc! {r#"
+                suite:
+                  include: shared/cli.yaml
+                ---
+                name: own test
+                extends: version
+                stdout: "mycmd 2.0\n"
+            "#},
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(
+            dir.path().join("shared/cli.yaml"),
+            indoc! {r#"
+                suite:
+                  include: [templates.json]
+                ---
+                name: help works
+                extends: help
+            "#},
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("shared/templates.json"),
+            r#"{"suite": {"templates": {
+                "help": {"cmd": "./mycmd --help"},
+                "version": {"cmd": "./mycmd --version"}
+            }}}"#,
+        )
+        .unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "suite::help works".to_string(),
+                    extends: Some("help".to_string()),
+                    cmd: "./mycmd --help".into(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "suite::own test".to_string(),
+                    extends: Some("version".to_string()),
+                    cmd: "./mycmd --version".into(),
+                    stdout: "mycmd 2.0\n".to_string(),
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_parse_file_include_relative_paths() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(&path, "suite:\n  include: shared/common.yaml\n").unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(
+            dir.path().join("shared/common.yaml"),
+            indoc! {r#"
+                suite:
+                  include_only: true
+                ---
+                name: table
+                cmd: cat
+                cases: rows.csv
+                ---
+                name: files
+                cmd: cat
+                for_each: "*.in"
+                ---
+                name: dir
+                cmd: ls ${suite_dir}
+            "#},
+        )
+        .unwrap();
+        fs::write(dir.path().join("shared/rows.csv"), "stdin,stdout\na,a\n").unwrap();
+        fs::write(dir.path().join("shared/one.in"), "1\n").unwrap();
+        fs::write(dir.path().join("shared/one.out"), "1\n").unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        let names = result
+            .tests
+            .iter()
+            .map(|test| &test.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "suite::table [row 1]",
+                "suite::files [one.in]",
+                "suite::dir"
+            ],
+            names
+        );
+        let shared = fs::canonicalize(dir.path().join("shared")).unwrap();
+        assert_eq!(
+            Cmd::from(format!("ls {}", shared.display()).as_str()),
+            result.tests[2].cmd
+        );
+    }
+
+    #[test]
+    fn test_parse_file_include_cycle() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.yaml");
+        fs::write(&path, "suite:\n  include: b.yaml\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "suite:\n  include: a.yaml\n").unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "a".to_string());
+
+        // THEN
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        assert_eq!(
+            format!(
+                "include cycle: `{0}/a.yaml` -> `{0}/b.yaml` -> `{0}/a.yaml`",
+                dir.display()
+            ),
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_file_for_each() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(
+            &path,
+            indoc! {r#"
+                name: judge
+                cmd: ./mysolution ${stem} < ${file}
+                for_each:
+                  files: tests/*.in
+                  stdout: ${stem}.expected
+            "#},
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("tests")).unwrap();
+        for (stem, input, output) in [("01", "1 2\n", "3\n"), ("02", "${HOME}\n", "$${x}\n")] {
+            fs::write(dir.path().join(format!("tests/{}.in", stem)), input).unwrap();
+            fs::write(dir.path().join(format!("tests/{}.expected", stem)), output).unwrap();
+        }
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        let tests = fs::canonicalize(dir.path().join("tests")).unwrap();
+        assert_eq!(
+            vec![
+                TestCase {
+                    name: "suite::judge [tests/01.in]".to_string(),
+                    cmd: format!("./mysolution 01 < {}/01.in", tests.display()).into(),
+                    stdin: "1 2\n".to_string(),
+                    stdout: "3\n".to_string(),
+                    ..Default::default()
+                },
+                TestCase {
+                    name: "suite::judge [tests/02.in]".to_string(),
+                    cmd: format!("./mysolution 02 < {}/02.in", tests.display()).into(),
+                    stdin: "$${HOME}\n".to_string(),
+                    stdout: "$$${x}\n".to_string(),
+                    ..Default::default()
+                },
+            ],
+            result.tests
+        );
+    }
+
+    #[test]
+    fn test_parse_file_for_each_missing_output() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(
+            &path,
+            "name: judge\ncmd: ./mysolution\nfor_each: \"*.in\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("01.in"), "1 2\n").unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string());
+
+        // THEN
+        assert_eq!(
+            format!(
+                "`{}`, document 1: test case `judge` has no expected output `{}` for `01.in`",
+                path.display(),
+                fs::canonicalize(dir.path())
+                    .unwrap()
+                    .join("01.out")
+                    .display()
+            ),
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_file_suite_paths() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.yaml");
+        fs::write(
+            &path,
+            "name: paths\ncmd: cat ${suite_file}\nstdout: ${suite_dir}\n",
+        )
+        .unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string()).unwrap();
+
+        // THEN
+        let path = fs::canonicalize(&path).unwrap();
+        assert_eq!(
+            Cmd::from(format!("cat {}", path.display())),
+            result.tests[0].cmd
+        );
+        assert_eq!(
+            path.parent().unwrap().to_string_lossy(),
+            result.tests[0].stdout
+        );
+    }
+
+    #[test]
+    fn test_from_reader_interact() {
+        // GIVEN
+        let mut doc = Cursor::new(indoc! {r#"
+            name: a repl
+            cmd: ./repl
+            interact:
+              - expect: "> "
+              - send: "1 + 1\n"
+              - expect: "^2$"
+                regex: true
+                timeout: 500ms
+              - send_signal: SIGINT
+              - send_eof
+        "#});
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser
+            .parse_reader(&mut doc, "mytestsuite".to_string())
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            vec![
+                InteractStep::Expect {
+                    expect: "> ".to_string(),
+                    regex: false,
+                    timeout: Duration::from_secs(5),
+                },
+                InteractStep::Send {
+                    send: "1 + 1\n".to_string(),
+                },
+                InteractStep::Expect {
+                    expect: "^2$".to_string(),
+                    regex: true,
+                    timeout: Duration::from_millis(500),
+                },
+                InteractStep::SendSignal {
+                    send_signal: Signal(libc::SIGINT),
+                },
+                InteractStep::SendEof(SendEof::SendEof),
+            ],
+            result.tests[0].interact
+        );
+    }
+
+    #[test]
+    fn test_parse_file_reports_all_errors() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tests.yaml");
+        fs::write(
+            &path,
+            indoc! {r#"
+                name: a
+                cmd: "true"
+                stdot: ok
+                ---
+                name: b
+                cmd: "true"
+                status: zero
+            "#},
+        )
+        .unwrap();
+
+        let parser = DefaultParser::new();
+
+        // WHEN
+        let result = parser.parse_file(&path, "suite".to_string());
+
+        // THEN
+        assert_eq!(
+            format!(
+                "`{0}`, document 1: unknown field `stdot`, did you mean `stdout`? at line 3 column 1\n\
+                 `{0}`, document 2: status: invalid type: string \"zero\", expected i32 at line 7 column 9",
+                path.display()
+            ),
+            result.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_from_reader_invalid_yaml() {
         // GIVEN
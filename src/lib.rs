@@ -3,17 +3,26 @@
 //! This is the library crate that supports the [pharaoh](https://github.com/Chewie/pharaoh) tool.
 //!
 //! The main function is [run], which will gather test cases from a [Gatherer], run them through a
-//! [Runner], and print the result via a [Printer].
+//! [Runner], and print the result via a [Printer]. Without running them, [lint] looks for likely
+//! mistakes in the test files with a [Linter].
 #![warn(missing_docs)]
 use anyhow::{Context, Result};
+use std::io::Write;
+
+use types::result::TestReport;
 
 pub mod gatherer;
+pub mod linter;
 pub mod printer;
 pub mod runner;
 pub mod types;
 
 #[doc(inline)]
-pub use gatherer::{Gatherer, YamlGatherer};
+pub use gatherer::{
+    CramGatherer, DirectoryGatherer, Gatherer, MarkdownGatherer, TrycmdGatherer, YamlGatherer,
+};
+#[doc(inline)]
+pub use linter::Linter;
 #[doc(inline)]
 pub use printer::{ColorPrinter, Printer};
 #[doc(inline)]
@@ -21,7 +30,43 @@ pub use runner::{DefaultRunner, Runner};
 
 /// Runs the test suite.
 pub fn run(gatherer: impl Gatherer, runner: impl Runner, printer: impl Printer) -> Result<()> {
-    let collection = gatherer.gather().context("Failed to parse YAML files")?;
+    run_and_report(&gatherer, runner, printer)?;
+
+    Ok(())
+}
+
+/// Runs the test suite, then writes the actual outputs of the failed tests back into them.
+///
+/// See [Gatherer::bless] for the tests that support it.
+pub fn bless(gatherer: impl Gatherer, runner: impl Runner, printer: impl Printer) -> Result<()> {
+    let report = run_and_report(&gatherer, runner, printer)?;
+
+    gatherer.bless(&report).context("Failed to bless tests")
+}
+
+/// Lints the test files and writes the findings, then tells whether none of them is a mistake.
+///
+/// See [linter::Lint::is_mistake] for the findings that are only information.
+pub fn lint<G: Gatherer>(
+    linter: &Linter<G>,
+    output: linter::Output,
+    writer: impl Write,
+) -> Result<bool> {
+    let findings = linter.lint().context("Failed to lint tests")?;
+
+    output
+        .write(&findings, writer)
+        .context("Failed to write findings")?;
+
+    Ok(!findings.iter().any(|finding| finding.lint.is_mistake()))
+}
+
+fn run_and_report(
+    gatherer: &impl Gatherer,
+    runner: impl Runner,
+    printer: impl Printer,
+) -> Result<TestReport> {
+    let collection = gatherer.gather().context("Failed to gather tests")?;
     let report = runner
         .run_all_tests(collection)
         .context("Failed to run tests")?;
@@ -30,7 +75,7 @@ pub fn run(gatherer: impl Gatherer, runner: impl Runner, printer: impl Printer)
         .print_report(&report)
         .context("Failed to write report")?;
 
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -38,7 +83,6 @@ mod tests {
     use super::*;
     use mockall::*;
 
-    use crate::types::result::TestReport;
     use crate::types::testcase::TestSuiteCollection;
 
     #[test]
@@ -71,6 +115,39 @@ mod tests {
         assert!(run_result.is_ok());
     }
 
+    #[test]
+    fn test_bless() {
+        // GIVEN
+        let mut gatherer = gatherer::MockGatherer::new();
+        gatherer
+            .expect_gather()
+            .times(1)
+            .return_once(move || Ok(a_testsuite_collection()));
+        gatherer
+            .expect_bless()
+            .with(predicate::eq(the_resulting_report()))
+            .times(1)
+            .return_once(move |_| Ok(()));
+
+        let mut runner = runner::MockRunner::new();
+        runner
+            .expect_run_all_tests()
+            .times(1)
+            .return_once(move |_| Ok(the_resulting_report()));
+
+        let mut printer = printer::MockPrinter::new();
+        printer
+            .expect_print_report()
+            .times(1)
+            .return_once(move |_| Ok(()));
+
+        // WHEN
+        let bless_result = bless(gatherer, runner, printer);
+
+        // THEN
+        assert!(bless_result.is_ok());
+    }
+
     fn a_testsuite_collection() -> TestSuiteCollection {
         TestSuiteCollection::default()
     }
@@ -0,0 +1,603 @@
+//! Checks of the test files that find likely mistakes without running anything
+//!
+//! The [Linter] gathers the tests the same way as a run, so that the files must be valid first,
+//! then reports each [Finding] of its [Lint]s. It runs no generator, so the tests that generators
+//! would make are reported as not checked instead.
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::gatherer::walker::{DefaultWalker, Walker};
+use crate::gatherer::{yaml, Gatherer, YamlGatherer};
+use crate::runner::executor::find_program;
+use crate::types::output_match::OutputMatch;
+use crate::types::testcase::{Cmd, TestCase, TestSuiteCollection};
+
+/// The directories whose files are only there to be used by the tests
+const FIXTURE_DIRS: &[&str] = &["**/fixtures/**"];
+
+/// The words that a shell runs without looking for a program
+const SHELL_WORDS: &[&str] = &[
+    "!", ".", ":", "[", "[[", "{", "alias", "bg", "break", "case", "cd", "command", "continue",
+    "echo", "eval", "exec", "exit", "export", "false", "fg", "for", "function", "getopts", "if",
+    "jobs", "kill", "local", "printf", "pwd", "read", "readonly", "return", "set", "shift",
+    "source", "test", "time", "times", "trap", "true", "type", "ulimit", "umask", "unalias",
+    "unset", "until", "wait", "while",
+];
+
+/// A kind of likely mistake in the test files
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    /// Several test cases of a suite have the same name
+    DuplicateName,
+    /// A test case runs no command, so that it always passes
+    AssertsNothing,
+    /// The program of a command is not found in `$PATH`
+    UnknownProgram,
+    /// An expected output lacks a trailing newline, or ends with a blank line
+    TrailingNewline,
+    /// A fixture file that no test references, or an `include_only` file that no file includes
+    UnusedFile,
+    /// A generator file, or a suite with a `generate` command, whose tests are not made to lint
+    ///
+    /// This is only information, which does not fail a lint.
+    NotChecked,
+}
+
+impl Lint {
+    /// Whether the finding is a likely mistake, rather than information that does not fail a lint
+    pub fn is_mistake(self) -> bool {
+        self != Lint::NotChecked
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Lint::DuplicateName => "duplicate-name",
+            Lint::AssertsNothing => "asserts-nothing",
+            Lint::UnknownProgram => "unknown-program",
+            Lint::TrailingNewline => "trailing-newline",
+            Lint::UnusedFile => "unused-file",
+            Lint::NotChecked => "not-checked",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A likely mistake, found in a test case such as `suite::name`, or in a file
+#[derive(Eq, PartialEq, Debug, Clone, Serialize)]
+pub struct Finding {
+    /// The kind of mistake
+    pub lint: Lint,
+    /// The test case, as `suite::name`, or the path of the file
+    pub location: String,
+    /// What is wrong
+    pub message: String,
+}
+
+impl Finding {
+    fn new(lint: Lint, location: impl fmt::Display, message: String) -> Self {
+        Finding {
+            lint,
+            location: location.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.location, self.message, self.lint)
+    }
+}
+
+/// How the findings are written: a line for each, or a JSON array for tools
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Output {
+    /// A line for each finding
+    Text,
+    /// A JSON array of the findings, with their `lint`, `location` and `message`
+    Json,
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(output: &str) -> Result<Self> {
+        match output {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            _ => bail!("unknown output `{}`", output),
+        }
+    }
+}
+
+impl Output {
+    /// Write the findings to `writer`
+    pub fn write(self, findings: &[Finding], mut writer: impl Write) -> Result<()> {
+        match self {
+            Output::Text => {
+                for finding in findings {
+                    writeln!(writer, "{}", finding)?;
+                }
+            }
+            Output::Json => {
+                serde_json::to_writer_pretty(&mut writer, findings)?;
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find likely mistakes in the YAML, JSON and TOML test files of a directory
+pub struct Linter<G: Gatherer> {
+    search_dir: String,
+    gatherer: G,
+}
+
+impl Linter<YamlGatherer<yaml::DefaultParser, DefaultWalker>> {
+    /// Constructs a new [Linter]
+    pub fn new(search_dir: String) -> Self {
+        let gatherer = YamlGatherer::without_generators(search_dir.clone());
+        Self::with_dependencies(search_dir, gatherer)
+    }
+}
+
+impl<G: Gatherer> Linter<G> {
+    fn with_dependencies(search_dir: String, gatherer: G) -> Self {
+        Linter {
+            search_dir,
+            gatherer,
+        }
+    }
+
+    /// Gather the tests, then report the findings of all the lints, in the order of the tests
+    pub fn lint(&self) -> Result<Vec<Finding>> {
+        let collection = self.gatherer.gather()?;
+
+        let mut findings = duplicate_names(&collection);
+        for testsuite in &collection.testsuites {
+            for test in &testsuite.tests {
+                findings.extend(
+                    check_test(test)
+                        .into_iter()
+                        .map(|(lint, message)| Finding::new(lint, &test.name, message)),
+                );
+            }
+        }
+        findings.extend(self.unused_files(&collection)?);
+        findings.extend(self.not_checked()?);
+
+        Ok(findings)
+    }
+
+    /// The generator files and the suites with a `generate` command, whose tests are only made by
+    /// running them
+    fn not_checked(&self) -> Result<Vec<Finding>> {
+        let parser = yaml::DefaultParser::without_generators();
+        let mut findings = vec![];
+        for path in DefaultWalker::with_patterns(yaml::PATTERNS).walk(&self.search_dir)? {
+            let message = if yaml::is_generator(&path) {
+                "the generator is not run, so its tests are not checked"
+            } else if parser.read_config(&path)?.generate.is_some() {
+                "the `generate` command is not run, so its tests are not checked"
+            } else {
+                continue;
+            };
+            findings.push(Finding::new(
+                Lint::NotChecked,
+                path.display(),
+                message.to_string(),
+            ));
+        }
+        Ok(findings)
+    }
+
+    /// The fixture files that neither the test files nor the tests mention, and the include-only
+    /// test files that no other test file mentions
+    fn unused_files(&self, collection: &TestSuiteCollection) -> Result<Vec<Finding>> {
+        let test_files = DefaultWalker::with_patterns(yaml::PATTERNS).walk(&self.search_dir)?;
+        let texts = test_files
+            .iter()
+            .map(|path| {
+                let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+                Ok((path.clone(), text))
+            })
+            .collect::<Result<BTreeMap<PathBuf, String>>>()?;
+        let tests = collection
+            .testsuites
+            .iter()
+            .flat_map(|testsuite| &testsuite.tests)
+            .map(mentions)
+            .collect::<Vec<_>>();
+        let mentioned = |path: &Path, needles: &[String]| {
+            texts
+                .iter()
+                .filter(|(text_path, _)| text_path.as_path() != path)
+                .map(|(_, text)| text)
+                .chain(&tests)
+                .any(|text| needles.iter().any(|needle| text.contains(needle.as_str())))
+        };
+
+        let parser = yaml::DefaultParser::without_generators();
+        let mut findings = vec![];
+        for path in &test_files {
+            if parser.read_config(path)?.include_only && !mentioned(path, &[file_name(path)]) {
+                let message = "the file is include-only, but no file includes it";
+                findings.push(Finding::new(
+                    Lint::UnusedFile,
+                    path.display(),
+                    message.to_string(),
+                ));
+            }
+        }
+        let fixtures = DefaultWalker::with_patterns(FIXTURE_DIRS).walk(&self.search_dir)?;
+        for path in fixtures.iter().filter(|path| path.is_file()) {
+            let needles = fixture_names(path);
+            if !mentioned(path, &needles) {
+                findings.push(Finding::new(
+                    Lint::UnusedFile,
+                    path.display(),
+                    "no test file or test mentions the fixture".to_string(),
+                ));
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// The names, such as `suite::name`, of more than one test case, once each
+fn duplicate_names(collection: &TestSuiteCollection) -> Vec<Finding> {
+    let mut counts: Vec<(String, usize)> = vec![];
+    for testsuite in &collection.testsuites {
+        for test in &testsuite.tests {
+            match counts.iter_mut().find(|(name, _)| *name == test.name) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((test.name.clone(), 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(location, count)| {
+            let message = format!("{} test cases have this name", count);
+            Finding::new(Lint::DuplicateName, location, message)
+        })
+        .collect()
+}
+
+/// The lints of a single test case
+fn check_test(test: &TestCase) -> Vec<(Lint, String)> {
+    let mut findings = vec![];
+
+    if test.script.is_none() && test.steps.is_empty() && is_noop(&test.cmd) {
+        let message = "the test runs no command, so it checks nothing".to_string();
+        findings.push((Lint::AssertsNothing, message));
+    }
+
+    if runs_in_posix_shell(test) {
+        let path = match test.env.get("PATH") {
+            Some(path) => Some(OsString::from(path)),
+            None => env::var_os("PATH"),
+        };
+        let commands = iter::once(&test.cmd).chain(test.steps.iter().map(|step| &step.cmd));
+        for program in commands.filter_map(program) {
+            let found = find_program(&program, path.as_deref()).is_ok();
+            if !found && !test.stubs.contains_key(&program) {
+                let message = format!("program `{}` was not found in $PATH", program);
+                findings.push((Lint::UnknownProgram, message));
+            }
+        }
+    }
+
+    if test.output_match == OutputMatch::Exact {
+        let expectations = [("stdout", &test.stdout), ("stderr", &test.stderr)];
+        let steps = test
+            .steps
+            .iter()
+            .flat_map(|step| vec![("stdout", &step.stdout), ("stderr", &step.stderr)]);
+        for (stream, expected) in expectations.iter().copied().chain(steps) {
+            if !expected.is_empty() && !expected.ends_with('\n') {
+                let message = format!("the expected {} does not end with a newline", stream);
+                findings.push((Lint::TrailingNewline, message));
+            } else if expected.ends_with("\n\n") {
+                let message = format!("the expected {} ends with a blank line", stream);
+                findings.push((Lint::TrailingNewline, message));
+            }
+        }
+    }
+
+    findings
+}
+
+fn is_noop(cmd: &Cmd) -> bool {
+    match cmd {
+        Cmd::Shell(line) => matches!(line.trim(), "" | "true" | ":"),
+        Cmd::Argv(argv) => argv.is_empty() || argv == &["true"],
+    }
+}
+
+/// Whether the commands of `test` are shell command lines whose first word names a program
+fn runs_in_posix_shell(test: &TestCase) -> bool {
+    match &test.shell {
+        None => true,
+        Some(shell) => shell
+            .0
+            .first()
+            .map(|program| file_name(Path::new(program)).ends_with("sh"))
+            .unwrap_or(false),
+    }
+}
+
+/// The program that a command runs by name, if it is not a shell word, a path or a variable
+fn program(cmd: &Cmd) -> Option<String> {
+    let word = match cmd {
+        Cmd::Argv(argv) => argv.first()?.clone(),
+        Cmd::Shell(line) => {
+            let words = shlex::split(line)?;
+            let word = words.into_iter().find(|word| !is_assignment(word))?;
+            let end = word.find(&[';', '|', '&', '<', '>', '(', ')'][..]);
+            word[..end.unwrap_or(word.len())].to_string()
+        }
+    };
+    let dynamic = word.contains(&['$', '`', '/'][..]);
+    if word.is_empty() || dynamic || SHELL_WORDS.contains(&word.as_str()) {
+        return None;
+    }
+    Some(word)
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// The text of a test case in which a file can be mentioned, once its variables are substituted
+fn mentions(test: &TestCase) -> String {
+    let commands = iter::once(&test.cmd).chain(test.steps.iter().map(|step| &step.cmd));
+    let mut text = vec![test.name.clone()];
+    text.extend(commands.map(|cmd| match cmd {
+        Cmd::Shell(line) => line.clone(),
+        Cmd::Argv(argv) => argv.join(" "),
+    }));
+    text.extend(test.script.clone());
+    text.extend(test.steps.iter().filter_map(|step| step.script.clone()));
+    text.extend(test.env.values().cloned());
+    text.extend(test.workdir.clone());
+    text.join("\n")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The names by which a fixture can be mentioned: its own, and those of its directories inside
+/// the fixtures directory
+fn fixture_names(path: &Path) -> Vec<String> {
+    path.ancestors()
+        .take_while(|ancestor| file_name(ancestor) != "fixtures")
+        .map(file_name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gatherer::MockGatherer;
+    use crate::types::testcase::{Step, Stub, TestSuite};
+    use indoc::indoc;
+
+    fn a_test(name: &str, cmd: &str) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            cmd: Cmd::from(cmd),
+            stdout: "ok\n".to_string(),
+            ..TestCase::default()
+        }
+    }
+
+    #[test]
+    fn test_check_test() {
+        // GIVEN
+        let fine = a_test("fine", "LC_ALL=C sort -r; echo done");
+        let noop = TestCase {
+            stdout: String::new(),
+            ..a_test("noop", "true")
+        };
+        let unknown = TestCase {
+            stubs: vec![("stubbed".to_string(), Stub::default())]
+                .into_iter()
+                .collect(),
+            steps: vec![Step {
+                cmd: Cmd::Argv(vec!["no-such-program".to_string()]),
+                stdout: "last\n\n".to_string(),
+                ..Step::default()
+            }],
+            ..a_test("unknown", "stubbed | ./local | $EDITOR")
+        };
+        let newline = TestCase {
+            stderr: "oops".to_string(),
+            ..a_test("newline", "cat")
+        };
+
+        // WHEN
+        let fine = check_test(&fine);
+        let noop = check_test(&noop);
+        let unknown = check_test(&unknown);
+        let newline = check_test(&newline);
+
+        // THEN
+        assert_eq!(Vec::<(Lint, String)>::new(), fine);
+        assert_eq!(
+            vec![(
+                Lint::AssertsNothing,
+                "the test runs no command, so it checks nothing".to_string()
+            )],
+            noop
+        );
+        assert_eq!(
+            vec![
+                (
+                    Lint::UnknownProgram,
+                    "program `no-such-program` was not found in $PATH".to_string()
+                ),
+                (
+                    Lint::TrailingNewline,
+                    "the expected stdout ends with a blank line".to_string()
+                ),
+            ],
+            unknown
+        );
+        assert_eq!(
+            vec![(
+                Lint::TrailingNewline,
+                "the expected stderr does not end with a newline".to_string()
+            )],
+            newline
+        );
+    }
+
+    #[test]
+    fn test_program() {
+        // GIVEN
+        let commands = [
+            Cmd::from("FOO=1 grep -q x"),
+            Cmd::from("cat<input"),
+            Cmd::from("cd /tmp && ls"),
+            Cmd::from("./myapp --help"),
+            Cmd::from("${tool} run"),
+            Cmd::from("'unbalanced"),
+            Cmd::Argv(vec!["wc".to_string(), "-l".to_string()]),
+        ];
+
+        // WHEN
+        let programs = commands.iter().map(program).collect::<Vec<_>>();
+
+        // THEN
+        assert_eq!(
+            vec![
+                Some("grep".to_string()),
+                Some("cat".to_string()),
+                None,
+                None,
+                None,
+                None,
+                Some("wc".to_string()),
+            ],
+            programs
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let search_dir = dir.path().to_string_lossy().into_owned();
+        fs::create_dir_all(dir.path().join("fixtures/inputs")).unwrap();
+        fs::write(dir.path().join("fixtures/config.toml"), "").unwrap();
+        fs::write(dir.path().join("fixtures/inputs/01.in"), "").unwrap();
+        fs::write(dir.path().join("fixtures/unused.txt"), "").unwrap();
+        let include_only = "suite:\n  include_only: true\n";
+        fs::write(dir.path().join("included.yaml"), include_only).unwrap();
+        fs::write(dir.path().join("forgotten.yaml"), include_only).unwrap();
+        fs::write(
+            dir.path().join("suite.yaml"),
+            "suite:\n  include: included.yaml\n",
+        )
+        .unwrap();
+
+        let mut gatherer = MockGatherer::new();
+        gatherer.expect_gather().times(1).return_once(|| {
+            Ok(TestSuiteCollection::new(vec![TestSuite {
+                name: "suite".to_string(),
+                tests: vec![
+                    a_test("suite::config", "cat fixtures/config.toml"),
+                    a_test("suite::judge [fixtures/inputs/01.in]", "cat"),
+                    a_test("suite::config", "cat"),
+                ],
+                services: vec![],
+                shared_shell: None,
+            }]))
+        });
+        let linter = Linter::with_dependencies(search_dir, gatherer);
+
+        // WHEN
+        let findings = linter.lint().unwrap();
+
+        // THEN
+        let location = |file: &str| dir.path().join(file).display().to_string();
+        assert_eq!(
+            vec![
+                Finding::new(
+                    Lint::DuplicateName,
+                    "suite::config",
+                    "2 test cases have this name".to_string()
+                ),
+                Finding::new(
+                    Lint::UnusedFile,
+                    location("forgotten.yaml"),
+                    "the file is include-only, but no file includes it".to_string()
+                ),
+                Finding::new(
+                    Lint::UnusedFile,
+                    location("fixtures/unused.txt"),
+                    "no test file or test mentions the fixture".to_string()
+                ),
+            ],
+            findings
+        );
+    }
+
+    #[test]
+    fn test_output_write() {
+        // GIVEN
+        let findings = vec![Finding::new(
+            Lint::DuplicateName,
+            "suite::a",
+            "2 test cases have this name".to_string(),
+        )];
+        let mut text = vec![];
+        let mut json = vec![];
+
+        // WHEN
+        Output::Text.write(&findings, &mut text).unwrap();
+        Output::Json.write(&findings, &mut json).unwrap();
+
+        // THEN
+        assert_eq!(
+            "suite::a: 2 test cases have this name [duplicate-name]\n",
+            String::from_utf8(text).unwrap()
+        );
+        assert_eq!(
+            indoc! {r#"
+                [
+                  {
+                    "lint": "duplicate-name",
+                    "location": "suite::a",
+                    "message": "2 test cases have this name"
+                  }
+                ]
+            "#},
+            String::from_utf8(json).unwrap()
+        );
+    }
+}
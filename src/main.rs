@@ -1,26 +1,130 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::*;
+use std::io::Write;
 
+use pharaoh::gatherer::Format;
+use pharaoh::linter::Output;
+use pharaoh::types::testcase::Limits;
 use pharaoh::ColorPrinter;
 use pharaoh::DefaultRunner;
-use pharaoh::YamlGatherer;
+use pharaoh::Linter;
 
 fn main() -> Result<()> {
     let matches = build_args().get_matches();
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        return lint(matches);
+    }
+    if matches.subcommand_matches("schema").is_some() {
+        return schema();
+    }
+
+    let search_dir = matches.value_of("search_dir").unwrap_or(".");
+    let limits = parse_limits(matches.values_of("limit").unwrap_or_default())?;
+
+    let format: Format = matches.value_of("format").unwrap_or("auto").parse()?;
+
+    let gatherer = format.gatherer(search_dir.to_string())?;
+    let runner = DefaultRunner::with_default_limits(limits);
+    let printer = ColorPrinter::new(std::io::stdout()).show_usage(matches.is_present("usage"));
+
+    match matches.is_present("bless") {
+        true => pharaoh::bless(gatherer, runner, printer),
+        false => pharaoh::run(gatherer, runner, printer),
+    }
+}
+
+/// Lint the test files, and exit with a failure if there is any finding
+fn lint(matches: &ArgMatches) -> Result<()> {
     let search_dir = matches.value_of("search_dir").unwrap_or(".");
+    let output: Output = matches.value_of("output").unwrap_or("text").parse()?;
 
-    let gatherer = YamlGatherer::new(search_dir.to_string());
-    let runner = DefaultRunner::new();
-    let printer = ColorPrinter::new(std::io::stdout());
+    let linter = Linter::new(search_dir.to_string());
+    if !pharaoh::lint(&linter, output, std::io::stdout())? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print the JSON Schema of the documents of test files
+fn schema() -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer_pretty(&mut stdout, &pharaoh::types::schema::schema())?;
+    writeln!(stdout)?;
+    Ok(())
+}
 
-    pharaoh::run(gatherer, runner, printer)
+/// Read `name=value` pairs the same way as a `limits` map in YAML
+fn parse_limits<'a>(values: impl Iterator<Item = &'a str>) -> Result<Limits> {
+    let yaml = values
+        .map(|value| value.replacen('=', ": ", 1))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if yaml.is_empty() {
+        return Ok(Limits::default());
+    }
+    serde_yaml::from_str(&yaml).context("Invalid --limit")
 }
 
 fn build_args() -> App<'static, 'static> {
-    clap::app_from_crate!().arg(
-        Arg::with_name("search_dir")
-            .index(1)
-            .help("The directory in which YAML are searched")
-            .default_value("."),
-    )
+    clap::app_from_crate!()
+        .arg(
+            Arg::with_name("search_dir")
+                .index(1)
+                .help("The directory in which tests are searched")
+                .default_value("."),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .possible_values(&["yaml", "dir", "md", "cram", "trycmd", "auto"])
+                .default_value("auto")
+                .help(
+                    "How the tests are stored: YAML, JSON or TOML files, directories of cmd, stdin, stdout, \
+                     stderr and status files, shell sessions in Markdown documents, cram \
+                     files, or trycmd case files",
+                ),
+        )
+        .arg(
+            Arg::with_name("bless")
+                .long("bless")
+                .help("Write the actual outputs of the failed tests back into Markdown documents"),
+        )
+        .arg(
+            Arg::with_name("usage")
+                .long("usage")
+                .help("Show the time and memory used by each test"),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .value_name("NAME=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "A default resource limit for the tests, e.g. cpu=2s, memory=512M, \
+                     file_size=10M, open_files=64 or processes=100",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Checks the YAML, JSON and TOML test files for likely mistakes, without running them")
+                .arg(
+                    Arg::with_name("search_dir")
+                        .index(1)
+                        .help("The directory in which tests are searched")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("How the findings are written: a line for each, or a JSON array"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Prints the JSON Schema of the documents of test files, for editors"),
+        )
 }
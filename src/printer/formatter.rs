@@ -2,11 +2,14 @@ use colored::Colorize;
 use indoc::formatdoc;
 use similar::{ChangeTag, TextDiff};
 
-use crate::types::result::TestResult;
+use crate::types::output_match::OutputMatch;
+use crate::types::result::{InteractFailure, TestResult, Usage};
+use crate::types::size;
 
 #[mockall::automock]
 pub trait Formatter {
     fn format_summary(&self, result: &TestResult) -> String;
+    fn format_usage(&self, usage: &Usage) -> String;
 }
 
 #[derive(Eq, PartialEq, Debug, Default)]
@@ -14,11 +17,54 @@ pub struct DefaultFormatter;
 
 impl Formatter for DefaultFormatter {
     fn format_summary(&self, result: &TestResult) -> String {
-        [self.format_status(result.expected_status, result.actual_status),
-            self.format_diff("stdout", &result.expected_stdout, &result.actual_stdout),
-            self.format_diff("stderr", &result.expected_stderr, &result.actual_stderr)]
+        let failed_step = match &result.failed_step {
+            Some(step) => format!("{} failed:\n", step.yellow()),
+            None => String::new(),
+        };
+        if let Some(error) = &result.error {
+            return format!(
+                "{}{}: {}\n{}",
+                failed_step,
+                "error".red(),
+                error,
+                self.format_service_logs(&result.service_logs)
+            );
+        }
+        [
+            failed_step,
+            self.format_limit_exceeded(&result.limit_exceeded),
+            self.format_capture_failure(&result.capture_failure),
+            self.format_interact_failure(&result.interact_failure),
+            self.format_budget(result),
+            self.format_status(result.expected_status, result.actual_status),
+            self.format_diff(
+                "stdout",
+                result.output_match,
+                &result.expected_stdout,
+                &result.actual_stdout,
+            ),
+            self.format_diff(
+                "stderr",
+                result.output_match,
+                &result.expected_stderr,
+                &result.actual_stderr,
+            ),
+            self.format_http(&result.http_failure, &result.http_requests),
+            self.format_calls(&result.calls_failure, &result.calls),
+            self.format_service_logs(&result.service_logs),
+        ]
         .join("")
     }
+
+    fn format_usage(&self, usage: &Usage) -> String {
+        format!(
+            "wall {:.2?}, user {:.2?}, sys {:.2?}, peak {}",
+            usage.wall_time,
+            usage.user_time,
+            usage.system_time,
+            size::format(usage.max_rss)
+        )
+    }
 }
 
 impl DefaultFormatter {
@@ -41,7 +87,128 @@ impl DefaultFormatter {
         }
     }
 
-    fn format_diff(&self, name: &str, expected: &str, actual: &str) -> String {
+    fn format_budget(&self, result: &TestResult) -> String {
+        let mut budget = String::new();
+        if let Some(max) = result.terminate_within {
+            match result.termination_time {
+                Some(actual) if actual <= max => (),
+                Some(actual) => budget.push_str(&format!(
+                    "{} too slow: expected at most {:.2?} after the last signal, actual {:.2?}\n",
+                    "termination".yellow(),
+                    max,
+                    actual
+                )),
+                None => budget.push_str(&format!(
+                    "{} not measured: no signal was sent\n",
+                    "termination".yellow()
+                )),
+            }
+        }
+        if let Some(max) = result
+            .max_duration
+            .filter(|max| result.usage.wall_time > *max)
+        {
+            budget.push_str(&format!(
+                "{} exceeded: expected at most {:.2?}, actual {:.2?}\n",
+                "duration".yellow(),
+                max,
+                result.usage.wall_time
+            ));
+        }
+        if let Some(max) = result.max_memory.filter(|max| result.usage.max_rss > *max) {
+            budget.push_str(&format!(
+                "{} exceeded: expected at most {}, actual {}\n",
+                "memory".yellow(),
+                size::format(max),
+                size::format(result.usage.max_rss)
+            ));
+        }
+        budget
+    }
+
+    fn format_limit_exceeded(&self, limit: &Option<String>) -> String {
+        match limit {
+            None => String::new(),
+            Some(limit) => format!("{} exceeded: {}\n", "limit".yellow(), limit),
+        }
+    }
+
+    fn format_capture_failure(&self, failure: &Option<String>) -> String {
+        match failure {
+            None => String::new(),
+            Some(failure) => format!("{} failed: {}\n", "capture".yellow(), failure),
+        }
+    }
+
+    fn format_interact_failure(&self, failure: &Option<InteractFailure>) -> String {
+        match failure {
+            None => String::new(),
+            Some(failure) => formatdoc!(
+                r#"
+            {} failed at step {} ({}): {}
+            transcript:
+            {}
+            "#,
+                "interaction".yellow(),
+                failure.index,
+                failure.step,
+                failure.reason,
+                failure.transcript.trim_end_matches('\n'),
+            ),
+        }
+    }
+
+    fn format_http(&self, failure: &Option<String>, requests: &[String]) -> String {
+        let mut http = String::new();
+        if let Some(failure) = failure {
+            http.push_str(&format!(
+                "{} differ: {}\n",
+                "http requests".yellow(),
+                failure
+            ));
+        }
+        if !requests.is_empty() {
+            http.push_str(&format!("{} received:\n", "http requests".yellow()));
+            for request in requests {
+                http.push_str(&format!("  {}\n", request));
+            }
+        }
+        http
+    }
+
+    fn format_calls(&self, failure: &Option<String>, calls: &[String]) -> String {
+        let mut summary = String::new();
+        if let Some(failure) = failure {
+            summary.push_str(&format!("{} differ: {}\n", "stub calls".yellow(), failure));
+        }
+        if !calls.is_empty() {
+            summary.push_str(&format!("{} received:\n", "stub calls".yellow()));
+            for call in calls {
+                summary.push_str(&format!("  {}\n", call));
+            }
+        }
+        summary
+    }
+
+    fn format_service_logs(&self, logs: &[(String, String)]) -> String {
+        logs.iter()
+            .map(|(name, logs)| {
+                format!("{} logs:\n{}\n", name.yellow(), logs.trim_end_matches('\n'))
+            })
+            .collect()
+    }
+
+    /// The diff of an output, unless it matches despite the text differing, as with a regex
+    fn format_diff(
+        &self,
+        name: &str,
+        output_match: OutputMatch,
+        expected: &str,
+        actual: &str,
+    ) -> String {
+        if output_match.matches(expected, actual) {
+            return String::new();
+        }
         let mut diff_summary = vec![];
         let diff = TextDiff::from_lines(expected, actual);
         if !diff.ops().to_vec().is_empty() {
@@ -70,6 +237,7 @@ impl DefaultFormatter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     impl TestResult {
         fn from_name(name: &str) -> Self {
@@ -77,10 +245,26 @@ mod tests {
                 name: name.to_string(),
                 expected_stdout: "".to_string(),
                 actual_stdout: "".to_string(),
+                output_match: OutputMatch::Exact,
                 expected_stderr: "".to_string(),
                 actual_stderr: "".to_string(),
                 expected_status: 0,
                 actual_status: 0,
+                interact_failure: None,
+                error: None,
+                limit_exceeded: None,
+                terminate_within: None,
+                termination_time: None,
+                max_duration: None,
+                max_memory: None,
+                usage: Default::default(),
+                service_logs: vec![],
+                http_requests: vec![],
+                http_failure: None,
+                calls: vec![],
+                calls_failure: None,
+                failed_step: None,
+                capture_failure: None,
             }
         }
     }
@@ -148,6 +332,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_summary_output_matches() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut cram = TestResult::from_name("cram");
+        cram.output_match = OutputMatch::Cram;
+        cram.expected_stdout = "abc[0-9]+ (re)\n".to_string();
+        cram.actual_stdout = "abc123\n".to_string();
+        cram.actual_status = 1;
+        let mut trycmd = TestResult::from_name("trycmd");
+        trycmd.output_match = OutputMatch::Trycmd;
+        trycmd.expected_stderr = "took [..]ms\n...\n".to_string();
+        trycmd.actual_stderr = "took 12ms\ndone\n".to_string();
+
+        // WHEN
+        let cram = formatter.format_summary(&cram);
+        let trycmd = formatter.format_summary(&trycmd);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} differs:
+            expected: 0
+            actual: 1
+            "#, "status code".yellow()},
+            cram
+        );
+        assert_eq!("", trycmd);
+    }
+
     #[test]
     fn test_format_summary_stderr_differs() {
         // GIVEN
@@ -176,6 +390,249 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_summary_error() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.expected_stdout = "foo".to_string();
+        result.error = Some("program `foo` was not found in $PATH".to_string());
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            format!("{}: program `foo` was not found in $PATH\n", "error".red()),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_over_budget() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.max_duration = Some(Duration::from_secs(1));
+        result.max_memory = Some(1 << 20);
+        result.usage = Usage {
+            wall_time: Duration::from_millis(1500),
+            max_rss: 3 << 20,
+            ..Default::default()
+        };
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} exceeded: expected at most 1.00s, actual 1.50s
+            {} exceeded: expected at most 1.0 MiB, actual 3.0 MiB
+            "#, "duration".yellow(), "memory".yellow()},
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_slow_termination() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.terminate_within = Some(Duration::from_secs(1));
+        result.termination_time = Some(Duration::from_secs(2));
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            format!(
+                "{} too slow: expected at most 1.00s after the last signal, actual 2.00s\n",
+                "termination".yellow()
+            ),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_usage() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let usage = Usage {
+            wall_time: Duration::from_millis(1500),
+            user_time: Duration::from_millis(1200),
+            system_time: Duration::from_micros(10500),
+            max_rss: 3 << 19,
+        };
+
+        // WHEN
+        let formatted = formatter.format_usage(&usage);
+
+        // THEN
+        assert_eq!(
+            "wall 1.50s, user 1.20s, sys 10.50ms, peak 1.5 MiB",
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_format_summary_limit_exceeded() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.limit_exceeded = Some("cpu time limit (2s)".to_string());
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            format!("{} exceeded: cpu time limit (2s)\n", "limit".yellow()),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_service_logs() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.actual_status = 1;
+        result.service_logs = vec![("api".to_string(), "GET /users 500\n".to_string())];
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} differs:
+            expected: 0
+            actual: 1
+            {} logs:
+            GET /users 500
+            "#, "status code".yellow(), "api".yellow()},
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_http_requests() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.http_failure = Some("unexpected request 2: `GET /users`".to_string());
+        result.http_requests = vec![
+            "POST /users -> 201".to_string(),
+            "GET /users -> 200".to_string(),
+        ];
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} differ: unexpected request 2: `GET /users`
+            {} received:
+              POST /users -> 201
+              GET /users -> 200
+            "#, "http requests".yellow(), "http requests".yellow()},
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_stub_calls() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.calls_failure =
+            Some("expected call 1 to be `git push`, but it was not made".to_string());
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            format!(
+                "{} differ: expected call 1 to be `git push`, but it was not made\n",
+                "stub calls".yellow()
+            ),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_failed_step() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.failed_step = Some("step 2 (commit)".to_string());
+        result.actual_status = 128;
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} failed:
+            {} differs:
+            expected: 0
+            actual: 128
+            "#, "step 2 (commit)".yellow(), "status code".yellow()},
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_capture_failure() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.capture_failure = Some("`id` did not match \"id: (\\d+)\"".to_string());
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            format!(
+                "{} failed: `id` did not match \"id: (\\d+)\"\n",
+                "capture".yellow()
+            ),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_format_summary_interact_failure() {
+        // GIVEN
+        let formatter = DefaultFormatter::new();
+        let mut result = TestResult::from_name("mytest");
+        result.interact_failure = Some(InteractFailure {
+            index: 2,
+            step: "expect \"> \"".to_string(),
+            reason: "timed out after 5s".to_string(),
+            transcript: "welcome\n".to_string(),
+        });
+
+        // WHEN
+        let summary = formatter.format_summary(&result);
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            {} failed at step 2 (expect "> "): timed out after 5s
+            transcript:
+            welcome
+            "#, "interaction".yellow()},
+            summary
+        );
+    }
+
     #[test]
     fn test_format_summary_everything_differs() {
         // GIVEN
@@ -184,10 +641,26 @@ mod tests {
             name: "mytest".into(),
             expected_stdout: "foo".to_string(),
             actual_stdout: "fou".to_string(),
+            output_match: OutputMatch::Exact,
             expected_stderr: "bar".to_string(),
             actual_stderr: "baz".to_string(),
             expected_status: 0,
             actual_status: 1,
+            interact_failure: None,
+            error: None,
+            limit_exceeded: None,
+            terminate_within: None,
+            termination_time: None,
+            max_duration: None,
+            max_memory: None,
+            usage: Default::default(),
+            service_logs: vec![],
+            http_requests: vec![],
+            http_failure: None,
+            calls: vec![],
+            calls_failure: None,
+            failed_step: None,
+            capture_failure: None,
         };
 
         // WHEN
@@ -21,6 +21,7 @@ pub trait Printer {
 pub struct ColorPrinter<F: Formatter, W: io::Write> {
     writer: RefCell<W>,
     formatter: F,
+    show_usage: bool,
 }
 
 impl<W: io::Write> ColorPrinter<DefaultFormatter, W> {
@@ -35,8 +36,15 @@ impl<F: Formatter, W: io::Write> ColorPrinter<F, W> {
         ColorPrinter {
             writer: RefCell::new(writer),
             formatter,
+            show_usage: false,
         }
     }
+
+    /// Show the resources used by each test, and not only by the ones with a time or memory budget
+    pub fn show_usage(mut self, show_usage: bool) -> Self {
+        self.show_usage = show_usage;
+        self
+    }
 }
 
 impl<F: Formatter, W: io::Write> Printer for ColorPrinter<F, W> {
@@ -78,11 +86,17 @@ impl<F: Formatter, W: io::Write> ColorPrinter<F, W> {
             true => "OK".green(),
             false => "FAILED".red(),
         };
+        let has_budget = result.max_duration.is_some() || result.max_memory.is_some();
+        let usage = match self.show_usage || has_budget {
+            true => format!(" ({})", self.formatter.format_usage(&result.usage)),
+            false => String::new(),
+        };
         writeln!(
             self.writer.borrow_mut(),
-            "test {} ... {}",
+            "test {} ... {}{}",
             result.name,
-            success_msg
+            success_msg,
+            usage
         )?;
 
         Ok(())
@@ -178,6 +192,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_print_report_with_usage() {
+        // GIVEN
+        let report = TestReport {
+            testsuites: vec![TestSuiteResult {
+                name: "mysuite".to_string(),
+                results: vec![TestResultBuilder::default()
+                    .name("mytestcase")
+                    .build()
+                    .unwrap()],
+            }],
+        };
+        let mut mock_formatter = formatter::MockFormatter::new();
+        mock_formatter
+            .expect_format_usage()
+            .times(1)
+            .return_const("wall 1.00s");
+
+        let printer = ColorPrinter::with_formatter(Vec::new(), mock_formatter).show_usage(true);
+
+        // WHEN
+        printer.print_report(&report).unwrap();
+
+        // THEN
+        assert_eq!(
+            formatdoc! {r#"
+            Running tests for mysuite
+            test mytestcase ... {ok} (wall 1.00s)
+            "#, ok="OK".green()},
+            std::str::from_utf8(&printer.writer.borrow()).unwrap()
+        );
+    }
+
     #[test]
     fn test_print_report_failing_test() {
         // GIVEN
@@ -1,34 +1,657 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use regex::bytes::Regex;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
+use std::fs;
 use std::io::Write;
-use std::process::{Command, Output, Stdio};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::thread;
+use std::time::Duration;
+use tempfile::NamedTempFile;
 
-use crate::types::testcase::TestCase;
+use super::limits;
+use super::mock_http::{self, MockServer, Request};
+use super::session::Session;
+use super::stubs::{self, Call, Stubs};
+use crate::types::result::{InteractFailure, Usage};
+use crate::types::testcase::{Cmd, InteractStep, Limits, Shell, SignalStep, TestCase};
+
+/// Everything that was observed while executing a [TestCase]
+pub struct Execution {
+    pub output: Output,
+    pub interact_failure: Option<InteractFailure>,
+    pub limit_exceeded: Option<String>,
+    pub usage: Usage,
+    pub termination_time: Option<Duration>,
+    pub http_requests: Vec<Request>,
+    pub http_failure: Option<String>,
+    pub calls: Vec<Call>,
+    pub calls_failure: Option<String>,
+}
+
+impl From<Output> for Execution {
+    fn from(output: Output) -> Self {
+        Execution {
+            output,
+            interact_failure: None,
+            limit_exceeded: None,
+            usage: Usage::default(),
+            termination_time: None,
+            http_requests: vec![],
+            http_failure: None,
+            calls: vec![],
+            calls_failure: None,
+        }
+    }
+}
 
 pub trait Executor {
-    fn execute(&self, testcase: &TestCase) -> Result<Output>;
+    fn execute(&self, testcase: &TestCase) -> Result<Execution>;
 }
 
 #[derive(Debug, Eq, PartialEq, Default)]
-pub struct SimpleExecutor {}
+pub struct SimpleExecutor {
+    default_limits: Limits,
+}
 
 impl SimpleExecutor {
     pub fn new() -> Self {
-        SimpleExecutor {}
+        Self::with_default_limits(Limits::default())
+    }
+
+    pub fn with_default_limits(default_limits: Limits) -> Self {
+        SimpleExecutor { default_limits }
+    }
+
+    fn command(
+        &self,
+        testcase: &TestCase,
+        script: Option<&Path>,
+        path: Option<&OsStr>,
+    ) -> Result<Command> {
+        let shell = testcase.shell.clone().unwrap_or_default();
+        if let Some(script) = script {
+            let interpreter = shell.interpreter();
+            let (program, args) = interpreter
+                .split_first()
+                .ok_or_else(|| anyhow!("shell is empty"))?;
+            let mut command = Command::new(program);
+            command.args(args).arg(script);
+            return Ok(command);
+        }
+
+        build_command(&testcase.cmd, &shell, path)
+    }
+
+    fn interact(&self, session: &mut Session, steps: &[InteractStep]) -> Option<InteractFailure> {
+        for (index, step) in steps.iter().enumerate() {
+            let outcome = match step {
+                InteractStep::Expect {
+                    expect,
+                    regex,
+                    timeout,
+                } => {
+                    let pattern = match regex {
+                        true => Regex::new(expect),
+                        false => Regex::new(&regex::escape(expect)),
+                    };
+                    pattern
+                        .map_err(|error| error.to_string())
+                        .and_then(|pattern| session.expect(&pattern, *timeout))
+                }
+                InteractStep::Send { send } => {
+                    session.send(send).map_err(|error| error.to_string())
+                }
+                InteractStep::SendSignal { send_signal } => session
+                    .signal(*send_signal)
+                    .map_err(|error| error.to_string()),
+                InteractStep::SendEof(_) => {
+                    session.close_stdin();
+                    Ok(())
+                }
+            };
+
+            if let Err(reason) = outcome {
+                return Some(self.abort(session, index, step, reason));
+            }
+        }
+        None
+    }
+
+    fn send_signals(&self, session: &mut Session, steps: &[SignalStep]) -> Option<InteractFailure> {
+        for (index, step) in steps.iter().enumerate() {
+            if let Err(reason) = self.send_signal(session, step) {
+                return Some(self.abort(session, index, step, reason));
+            }
+        }
+        None
+    }
+
+    fn send_signal(&self, session: &mut Session, step: &SignalStep) -> Result<(), String> {
+        if let Some(pattern) = &step.after_stdout {
+            let pattern = Regex::new(pattern).map_err(|error| error.to_string())?;
+            session.expect_stdout(&pattern, step.timeout)?;
+        }
+        if let Some(after) = step.after {
+            thread::sleep(after);
+        }
+        session
+            .signal(step.signal)
+            .map_err(|error| error.to_string())
+    }
+
+    /// Kill the command after one of its steps failed
+    fn abort(
+        &self,
+        session: &mut Session,
+        index: usize,
+        step: &impl Display,
+        reason: String,
+    ) -> InteractFailure {
+        let transcript = session.transcript();
+        session.kill();
+        InteractFailure {
+            index: index + 1,
+            step: step.to_string(),
+            reason,
+            transcript,
+        }
     }
 }
 
 impl Executor for SimpleExecutor {
-    fn execute(&self, testcase: &TestCase) -> Result<Output> {
-        let mut child = Command::new("/bin/sh")
-            .args(vec!["-c", &testcase.cmd])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let child_stdin = child.stdin.as_mut().unwrap();
-        child_stdin.write_all(testcase.stdin.as_bytes())?;
-
-        Ok(child.wait_with_output()?)
+    fn execute(&self, testcase: &TestCase) -> Result<Execution> {
+        let script = testcase.script.as_deref().map(write_script).transpose()?;
+        let expected_calls = testcase.expect_calls.as_deref().unwrap_or_default();
+        let stubs = match testcase.stubs.is_empty() {
+            true => None,
+            false => Some(Stubs::create(
+                &testcase.stubs,
+                &stubs::reading_stdin(expected_calls),
+            )?),
+        };
+        let mut path = testcase
+            .env
+            .get("PATH")
+            .map(OsString::from)
+            .or_else(|| env::var_os("PATH"));
+        if let Some(stubs) = &stubs {
+            path = Some(stubs::path(stubs, path.as_deref())?);
+        }
+
+        let mut command = self.command(
+            testcase,
+            script.as_ref().map(NamedTempFile::path),
+            path.as_deref(),
+        )?;
+        let mock_server = testcase
+            .mock_http
+            .as_ref()
+            .map(MockServer::start)
+            .transpose()?;
+        if let Some(mock_server) = &mock_server {
+            command.env("MOCK_HTTP_URL", mock_server.url());
+        }
+        command.envs(&testcase.env);
+        if let Some(workdir) = &testcase.workdir {
+            command.current_dir(workdir);
+        }
+        if stubs.is_some() {
+            command.env("PATH", path.unwrap_or_default());
+        }
+        let limits = testcase.limits.or(&self.default_limits);
+        limits::apply(&mut command, limits);
+        let mut session = Session::spawn(&mut command)
+            .with_context(|| format!("failed to run {:?}", command.get_program()))?;
+
+        let interact_failure = if testcase.interact.is_empty() {
+            session.feed(&testcase.stdin)?;
+            None
+        } else {
+            if !testcase.stdin.is_empty() {
+                session.send(&testcase.stdin)?;
+            }
+            self.interact(&mut session, &testcase.interact)
+        };
+        let interact_failure =
+            interact_failure.or_else(|| self.send_signals(&mut session, &testcase.signals));
+
+        let last_signal = session.last_signal();
+        // A command that ignores the signals is killed once it may no longer exit
+        let termination_time = match (last_signal, testcase.terminate_within) {
+            (Some(sent), Some(max)) => Some(session.exit_within(sent, max)?),
+            _ => None,
+        };
+        let (output, usage) = session.wait()?;
+        let http_requests = mock_server
+            .as_ref()
+            .map(MockServer::requests)
+            .unwrap_or_default();
+        let http_failure = testcase
+            .mock_http
+            .as_ref()
+            .and_then(|mock_http| mock_http.expect_requests.as_ref())
+            .and_then(|expected| mock_http::check(expected, &http_requests));
+        let calls = match &stubs {
+            Some(stubs) => stubs.calls()?,
+            None => vec![],
+        };
+        let calls_failure = testcase
+            .expect_calls
+            .as_ref()
+            .and_then(|expected| stubs::check(expected, &calls));
+        Ok(Execution {
+            limit_exceeded: limits::exceeded(&limits, &output),
+            output,
+            interact_failure,
+            usage,
+            termination_time: termination_time.or_else(|| last_signal.map(|sent| sent.elapsed())),
+            http_requests,
+            http_failure,
+            calls,
+            calls_failure,
+        })
+    }
+}
+
+/// Build the [Command] running `cmd`, through `shell` unless it is an argv list
+///
+/// The program of an argv list is searched in `path`.
+pub fn build_command(cmd: &Cmd, shell: &Shell, path: Option<&OsStr>) -> Result<Command> {
+    match cmd {
+        Cmd::Shell(cmd) => {
+            let (program, args) = shell
+                .0
+                .split_first()
+                .ok_or_else(|| anyhow!("shell is empty"))?;
+            let mut command = Command::new(program);
+            command.args(args).arg(cmd);
+            Ok(command)
+        }
+        Cmd::Argv(argv) => {
+            let (program, args) = argv
+                .split_first()
+                .ok_or_else(|| anyhow!("cmd is an empty list"))?;
+            let mut command = Command::new(find_program(program, path)?);
+            command.args(args);
+            Ok(command)
+        }
+    }
+}
+
+fn write_script(script: &str) -> Result<NamedTempFile> {
+    let mut file = NamedTempFile::new()?;
+    file.write_all(script.as_bytes())?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// Resolve a program the same way a shell would, by searching `path` for bare names
+pub(crate) fn find_program(program: &str, path: Option<&OsStr>) -> Result<PathBuf> {
+    if program.contains('/') {
+        return Ok(PathBuf::from(program));
+    }
+    path.and_then(|paths| {
+        env::split_paths(paths)
+            .map(|dir| dir.join(program))
+            .find(|path| is_executable(path))
+    })
+    .with_context(|| format!("program `{}` was not found in $PATH", program))
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::signal::Signal;
+    use crate::types::testcase::{ExpectedCall, ExpectedRequest, MockHttp, Route, Stub};
+
+    fn expect(text: &str) -> InteractStep {
+        InteractStep::Expect {
+            expect: text.to_string(),
+            regex: false,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_execute_argv() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "argv".to_string(),
+            cmd: Cmd::Argv(vec![
+                "printf".to_string(),
+                "%s\n".to_string(),
+                "a file with 'quotes' and spaces".to_string(),
+            ]),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(
+            b"a file with 'quotes' and spaces\n".to_vec(),
+            execution.output.stdout
+        );
+    }
+
+    #[test]
+    fn test_execute_argv_program_not_found() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "argv".to_string(),
+            cmd: Cmd::Argv(vec!["pharaoh-no-such-program".to_string()]),
+            ..Default::default()
+        };
+
+        // WHEN
+        let result = SimpleExecutor::new().execute(&testcase);
+
+        // THEN
+        assert_eq!(
+            "program `pharaoh-no-such-program` was not found in $PATH",
+            result.err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_execute_custom_shell() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "shell".to_string(),
+            cmd: "false; echo unreachable".into(),
+            shell: Some(Shell(vec![
+                "/bin/sh".to_string(),
+                "-e".to_string(),
+                "-c".to_string(),
+            ])),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"".to_vec(), execution.output.stdout);
+        assert_eq!(Some(1), execution.output.status.code());
+    }
+
+    #[test]
+    fn test_execute_script() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "script".to_string(),
+            script: Some("read line\necho \"script says $line\"\n".to_string()),
+            stdin: "hi\n".to_string(),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"script says hi\n".to_vec(), execution.output.stdout);
+    }
+
+    #[test]
+    fn test_execute_script_with_shell_flags() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "script".to_string(),
+            script: Some("false\necho unreachable\n".to_string()),
+            shell: Some(Shell(vec!["sh".to_string(), "-ec".to_string()])),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"".to_vec(), execution.output.stdout);
+        assert_eq!(Some(1), execution.output.status.code());
+    }
+
+    #[test]
+    fn test_execute_mock_http() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "mock http".to_string(),
+            cmd: "curl -s \"$MOCK_HTTP_URL/users\"; curl -s -X DELETE \"$MOCK_HTTP_URL/users\""
+                .into(),
+            mock_http: Some(MockHttp {
+                routes: vec![Route {
+                    method: "GET".to_string(),
+                    path: "/users".to_string(),
+                    status: 200,
+                    body: "alice\n".to_string(),
+                    ..Default::default()
+                }],
+                expect_requests: Some(vec![ExpectedRequest {
+                    method: "GET".to_string(),
+                    path: "/users".to_string(),
+                    body: None,
+                }]),
+            }),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"alice\n".to_vec(), execution.output.stdout);
+        assert_eq!(
+            vec!["GET /users -> 200", "DELETE /users -> 404"],
+            execution
+                .http_requests
+                .iter()
+                .map(Request::to_string)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("unexpected request 2: `DELETE /users`".to_string()),
+            execution.http_failure
+        );
+    }
+
+    #[test]
+    fn test_execute_stubs() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "stubs".to_string(),
+            cmd: Cmd::Argv(vec![
+                "git".to_string(),
+                "push".to_string(),
+                "origin".to_string(),
+            ]),
+            stubs: [(
+                "git".to_string(),
+                Stub {
+                    stdout: "Everything up-to-date\n".to_string(),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            expect_calls: Some(vec![ExpectedCall {
+                argv: vec!["git".to_string(), "pull".to_string()],
+                stdin: None,
+            }]),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"Everything up-to-date\n".to_vec(), execution.output.stdout);
+        assert_eq!(
+            Some("expected call 1 to be `git pull`, actual `git push origin`".to_string()),
+            execution.calls_failure
+        );
+    }
+
+    #[test]
+    fn test_execute_env() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "env".to_string(),
+            cmd: "echo \"$GREETING\"".into(),
+            env: [("GREETING".to_string(), "hello".to_string())].into(),
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"hello\n".to_vec(), execution.output.stdout);
+    }
+
+    #[test]
+    fn test_execute_default_limits() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "limits".to_string(),
+            cmd: "ulimit -n; ulimit -t".into(),
+            limits: Limits {
+                open_files: Some(32),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let executor = SimpleExecutor::with_default_limits(Limits {
+            cpu: Some(Duration::from_secs(3)),
+            open_files: Some(64),
+            ..Default::default()
+        });
+
+        // WHEN
+        let execution = executor.execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(b"32\n3\n".to_vec(), execution.output.stdout);
+    }
+
+    #[test]
+    fn test_execute_file_size_limit_exceeded() {
+        // GIVEN
+        let tmp = tempfile::tempdir().unwrap();
+        let testcase = TestCase {
+            name: "limits".to_string(),
+            cmd: format!(
+                "head -c 4096 /dev/zero > {}",
+                tmp.path().join("big").display()
+            )
+            .into(),
+            limits: Limits {
+                file_size: Some(1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(
+            Some("file size limit (1.0 KiB)".to_string()),
+            execution.limit_exceeded
+        );
+    }
+
+    #[test]
+    fn test_execute_signals() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "graceful".to_string(),
+            cmd: "trap 'echo cleanup; exit 3' INT; echo ready; while true; do sleep 0.01; done"
+                .into(),
+            signals: vec![SignalStep {
+                signal: Signal(libc::SIGINT),
+                after_stdout: Some("ready".to_string()),
+                after: Some(Duration::from_millis(50)),
+                timeout: Duration::from_secs(5),
+            }],
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(None, execution.interact_failure);
+        assert_eq!(b"ready\ncleanup\n".to_vec(), execution.output.stdout);
+        assert_eq!(Some(3), execution.output.status.code());
+        assert!(execution.termination_time.unwrap() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_execute_interact() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "repl".to_string(),
+            cmd: "while printf '> ' && read line; do echo \"[$line]\"; done".into(),
+            interact: vec![
+                expect("> "),
+                InteractStep::Send {
+                    send: "a\n".to_string(),
+                },
+                expect("[a]"),
+                InteractStep::SendEof(crate::types::testcase::SendEof::SendEof),
+            ],
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(None, execution.interact_failure);
+        assert_eq!(b"> [a]\n> ".to_vec(), execution.output.stdout);
+    }
+
+    #[test]
+    fn test_execute_interact_failure() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "sleeper".to_string(),
+            cmd: "echo starting; sleep 5".into(),
+            interact: vec![
+                InteractStep::Expect {
+                    expect: "ready".to_string(),
+                    regex: false,
+                    timeout: Duration::from_millis(100),
+                },
+                InteractStep::SendSignal {
+                    send_signal: Signal(libc::SIGINT),
+                },
+            ],
+            ..Default::default()
+        };
+
+        // WHEN
+        let execution = SimpleExecutor::new().execute(&testcase).unwrap();
+
+        // THEN
+        assert_eq!(
+            Some(InteractFailure {
+                index: 1,
+                step: "expect \"ready\"".to_string(),
+                reason: "timed out after 100ms".to_string(),
+                transcript: "starting\n".to_string(),
+            }),
+            execution.interact_failure
+        );
     }
 }
@@ -0,0 +1,188 @@
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, Output};
+
+use crate::types::size;
+use crate::types::testcase::Limits;
+
+/// Lower a resource limit of the current process, without trying to raise its hard limit
+macro_rules! set_limit {
+    ($resource:expr, $soft:expr, $hard:expr) => {{
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if libc::getrlimit($resource, &mut current) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let hard = ($hard as libc::rlim_t).min(current.rlim_max);
+        let wanted = libc::rlimit {
+            rlim_cur: ($soft as libc::rlim_t).min(hard),
+            rlim_max: hard,
+        };
+        if libc::setrlimit($resource, &wanted) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }};
+}
+
+/// Set the given limits on the command, right before it is executed
+pub fn apply(command: &mut Command, limits: Limits) {
+    if limits == Limits::default() {
+        return;
+    }
+    // SAFETY: only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(cpu) = limits.cpu {
+                // The hard limit leaves a second to handle SIGXCPU before being killed
+                let seconds = cpu.as_secs() + u64::from(cpu.subsec_nanos() > 0);
+                set_limit!(libc::RLIMIT_CPU, seconds, seconds + 1);
+            }
+            if let Some(memory) = limits.memory {
+                set_limit!(libc::RLIMIT_AS, memory, memory);
+            }
+            if let Some(file_size) = limits.file_size {
+                set_limit!(libc::RLIMIT_FSIZE, file_size, file_size);
+            }
+            if let Some(open_files) = limits.open_files {
+                set_limit!(libc::RLIMIT_NOFILE, open_files, open_files);
+            }
+            if let Some(processes) = limits.processes {
+                set_limit!(libc::RLIMIT_NPROC, processes, processes);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// The limit that the kernel stopped the command for, if any
+///
+/// Only the CPU time and file size limits are reported, by their dedicated signal. The other
+/// ones only make some system calls fail, which the command may handle in any way, so they are
+/// never guessed.
+pub fn exceeded(limits: &Limits, output: &Output) -> Option<String> {
+    // Shells report a child killed by a signal with an exit code of 128 + signal
+    let signal = output.status.signal().or_else(|| {
+        output
+            .status
+            .code()
+            .filter(|code| *code > 128)
+            .map(|code| code - 128)
+    });
+
+    match signal {
+        Some(libc::SIGXCPU) => limits.cpu.map(|cpu| format!("cpu time limit ({:?})", cpu)),
+        Some(libc::SIGXFSZ) => limits
+            .file_size
+            .map(|file_size| format!("file size limit ({})", size::format(file_size))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::ExitStatus;
+    use std::time::Duration;
+
+    fn an_output(status: ExitStatus, stderr: &str) -> Output {
+        Output {
+            status,
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_exceeded_cpu() {
+        // GIVEN
+        let limits = Limits {
+            cpu: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        let output = an_output(ExitStatus::from_raw(libc::SIGXCPU), "");
+
+        // WHEN
+        let result = exceeded(&limits, &output);
+
+        // THEN
+        assert_eq!(Some("cpu time limit (2s)".to_string()), result);
+    }
+
+    #[test]
+    fn test_exceeded_file_size_in_shell() {
+        // GIVEN
+        let limits = Limits {
+            file_size: Some(1024),
+            ..Default::default()
+        };
+        let output = an_output(ExitStatus::from_raw((128 + libc::SIGXFSZ) << 8), "");
+
+        // WHEN
+        let result = exceeded(&limits, &output);
+
+        // THEN
+        assert_eq!(Some("file size limit (1.0 KiB)".to_string()), result);
+    }
+
+    #[test]
+    fn test_not_exceeded_without_signal() {
+        // GIVEN
+        let limits = Limits {
+            memory: Some(1 << 20),
+            open_files: Some(16),
+            processes: Some(4),
+            ..Default::default()
+        };
+        let crashed = an_output(ExitStatus::from_raw(libc::SIGSEGV), "");
+        let aborted = an_output(ExitStatus::from_raw((128 + libc::SIGABRT) << 8), "");
+        let printed = an_output(
+            ExitStatus::from_raw(1 << 8),
+            "Too many open files\ncannot fork\nCannot allocate memory\n",
+        );
+
+        // WHEN
+        let results = [crashed, aborted, printed]
+            .iter()
+            .map(|output| exceeded(&limits, output))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_eq!(vec![None, None, None], results);
+    }
+
+    #[test]
+    fn test_not_exceeded() {
+        // GIVEN
+        let limits = Limits {
+            cpu: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        let output = an_output(ExitStatus::from_raw(libc::SIGXFSZ), "");
+
+        // WHEN
+        let result = exceeded(&limits, &output);
+
+        // THEN
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn test_apply() {
+        // GIVEN
+        let mut command = Command::new("/bin/sh");
+        command.args(vec!["-c", "ulimit -n"]);
+        let limits = Limits {
+            open_files: Some(32),
+            ..Default::default()
+        };
+
+        // WHEN
+        apply(&mut command, limits);
+        let output = command.output().unwrap();
+
+        // THEN
+        assert_eq!(b"32\n".to_vec(), output.stdout);
+    }
+}
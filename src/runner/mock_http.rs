@@ -0,0 +1,331 @@
+use anyhow::Result;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::types::testcase::{ExpectedRequest, MockHttp, Route};
+
+/// How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request received by a [MockServer], along with the status it was answered with
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+    pub status: u16,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} -> {}", self.method, self.path, self.status)
+    }
+}
+
+/// A HTTP server answering canned responses on localhost, stopped when dropped
+///
+/// Connections are served one at a time, and closed after each response.
+pub struct MockServer {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start(mock_http: &MockHttp) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let address = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(vec![]));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let routes = mock_http.routes.clone();
+            let requests = requests.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // A client that goes away mid-request is none of our business
+                    if let Ok(stream) = stream {
+                        let _ = serve(stream, &routes, &requests);
+                    }
+                }
+            })
+        };
+
+        Ok(MockServer {
+            address,
+            requests,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// The base URL of the server, without a trailing slash
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the server up from accept, so that it notices it was stopped
+        let _ = TcpStream::connect(self.address);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Compare the requests that were received to the expected ones
+///
+/// On mismatch, a description of the first difference is returned.
+pub fn check(expected: &[ExpectedRequest], actual: &[Request]) -> Option<String> {
+    for (index, expected) in expected.iter().enumerate() {
+        let actual = match actual.get(index) {
+            Some(actual) => actual,
+            None => {
+                return Some(format!(
+                    "expected request {} to be `{}`, but it was not made",
+                    index + 1,
+                    expected
+                ))
+            }
+        };
+        if expected.method != actual.method || expected.path != actual.path {
+            return Some(format!(
+                "expected request {} to be `{}`, actual `{} {}`",
+                index + 1,
+                expected,
+                actual.method,
+                actual.path
+            ));
+        }
+        if let Some(body) = expected.body.as_ref().filter(|body| **body != actual.body) {
+            return Some(format!(
+                "expected request {} (`{}`) to have body {:?}, actual {:?}",
+                index + 1,
+                expected,
+                body,
+                actual.body
+            ));
+        }
+    }
+    actual.get(expected.len()).map(|unexpected| {
+        format!(
+            "unexpected request {}: `{} {}`",
+            expected.len() + 1,
+            unexpected.method,
+            unexpected.path
+        )
+    })
+}
+
+fn serve(stream: TcpStream, routes: &[Route], requests: &Mutex<Vec<Request>>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Ok(()),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let route = routes.iter().find(|route| matches(route, &method, &path));
+    let status = route.map_or(404, |route| route.status);
+    // The request is recorded before answering, so that it is known once the client got its answer
+    requests.lock().unwrap().push(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        status,
+    });
+
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    let body = route.map_or("", |route| route.body.as_str());
+    if let Some(route) = route {
+        for (name, value) in &route.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    response.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    ));
+    (&stream).write_all(response.as_bytes())?;
+    (&stream).flush()
+}
+
+/// Whether `route` answers a request, ignoring the query string unless the route has one
+fn matches(route: &Route, method: &str, path: &str) -> bool {
+    let path = match route.path.contains('?') {
+        true => path,
+        false => path.split('?').next().unwrap_or(path),
+    };
+    route.method.eq_ignore_ascii_case(method) && route.path == path
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(server: &MockServer, raw: &str) -> String {
+        let mut stream = TcpStream::connect(server.address).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn route(method: &str, path: &str, status: u16, body: &str) -> Route {
+        Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            headers: [("Content-Type".to_string(), "text/plain".to_string())].into(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_serve_routes() {
+        // GIVEN
+        let server = MockServer::start(&MockHttp {
+            routes: vec![
+                route("GET", "/users", 200, "alice\n"),
+                route("POST", "/users", 201, "created\n"),
+            ],
+            expect_requests: None,
+        })
+        .unwrap();
+
+        // WHEN
+        let get = request(&server, "GET /users?page=2 HTTP/1.1\r\nHost: x\r\n\r\n");
+        let post = request(
+            &server,
+            "POST /users HTTP/1.1\r\nContent-Length: 5\r\n\r\nalice",
+        );
+        let missing = request(&server, "DELETE /users HTTP/1.1\r\n\r\n");
+
+        // THEN
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 6\r\n\
+             Connection: close\r\n\r\nalice\n",
+            get
+        );
+        assert!(post.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(
+            vec![
+                "GET /users?page=2 -> 200".to_string(),
+                "POST /users -> 201".to_string(),
+                "DELETE /users -> 404".to_string(),
+            ],
+            server
+                .requests()
+                .iter()
+                .map(Request::to_string)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("alice", server.requests()[1].body);
+    }
+
+    #[test]
+    fn test_check() {
+        // GIVEN
+        let expected = vec![ExpectedRequest {
+            method: "POST".to_string(),
+            path: "/users".to_string(),
+            body: Some("alice".to_string()),
+        }];
+        let received = |method: &str, body: &str| Request {
+            method: method.to_string(),
+            path: "/users".to_string(),
+            body: body.to_string(),
+            status: 200,
+        };
+
+        // WHEN
+        let matching = check(&expected, &[received("POST", "alice")]);
+        let missing = check(&expected, &[]);
+        let wrong_method = check(&expected, &[received("GET", "")]);
+        let wrong_body = check(&expected, &[received("POST", "bob")]);
+        let unexpected = check(&[], &[received("GET", "")]);
+
+        // THEN
+        assert_eq!(None, matching);
+        assert_eq!(
+            Some("expected request 1 to be `POST /users`, but it was not made".to_string()),
+            missing
+        );
+        assert_eq!(
+            Some("expected request 1 to be `POST /users`, actual `GET /users`".to_string()),
+            wrong_method
+        );
+        assert_eq!(
+            Some(
+                "expected request 1 (`POST /users`) to have body \"alice\", actual \"bob\""
+                    .to_string()
+            ),
+            wrong_body
+        );
+        assert_eq!(
+            Some("unexpected request 1: `GET /users`".to_string()),
+            unexpected
+        );
+    }
+}
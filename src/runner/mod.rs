@@ -1,11 +1,20 @@
 //! Runs test cases to produce a [TestReport]
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{self, ExitStatus, Output};
 
-mod executor;
+pub(crate) mod executor;
+mod limits;
+mod mock_http;
+mod services;
+mod session;
+mod stubs;
 
 use crate::types::result::{TestReport, TestResult, TestSuiteResult};
-use crate::types::testcase::{TestCase, TestSuite, TestSuiteCollection};
+use crate::types::testcase::{Cmd, Limits, SharedShell, TestCase, TestSuite, TestSuiteCollection};
+use crate::types::vars::{self, Vars};
 use executor::{Executor, SimpleExecutor};
+use services::Services;
 
 /// A trait to regroup all struct able to run a [TestSuiteCollection]
 #[mockall::automock]
@@ -37,6 +46,11 @@ impl DefaultRunner<SimpleExecutor> {
     pub fn new() -> Self {
         Self::with_executor(SimpleExecutor::new())
     }
+
+    /// Constructs a new [DefaultRunner], with limits for the tests that do not set their own
+    pub fn with_default_limits(limits: Limits) -> Self {
+        Self::with_executor(SimpleExecutor::with_default_limits(limits))
+    }
 }
 
 impl<E: Executor> DefaultRunner<E> {
@@ -45,20 +59,280 @@ impl<E: Executor> DefaultRunner<E> {
     }
 
     fn run_testsuite(&self, testsuite: TestSuite) -> Result<TestSuiteResult> {
+        // The services are stopped when dropped, whatever happens to the tests
+        let mut services = match Services::start(&testsuite.services) {
+            Ok(services) => services,
+            Err(error) => {
+                return Ok(TestSuiteResult {
+                    name: testsuite.name,
+                    results: testsuite
+                        .tests
+                        .into_iter()
+                        .map(|testcase| TestResult::from_error(testcase, anyhow!("{:#}", error)))
+                        .collect(),
+                })
+            }
+        };
+        services.logs();
+
+        if let Some(shared_shell) = &testsuite.shared_shell {
+            let mut tests = testsuite.tests;
+            for testcase in &mut tests {
+                services.prepare(testcase);
+            }
+            return Ok(TestSuiteResult {
+                results: self.run_in_shared_shell(tests, shared_shell)?,
+                name: testsuite.name,
+            });
+        }
+
+        // Captured variables are shared by the following tests of the suite
+        let mut vars = Vars::new();
         Ok(TestSuiteResult {
             name: testsuite.name.clone(),
             results: testsuite
                 .tests
                 .into_iter()
-                .map(|testcase| self.output_from_testcase(testcase))
+                .map(|mut testcase| {
+                    services.prepare(&mut testcase);
+                    let mut result = self.run_testcase(testcase, &mut vars)?;
+                    let logs = services.logs();
+                    if !result.is_successful() {
+                        result.service_logs = logs;
+                    }
+                    Ok(result)
+                })
                 .collect::<Result<_>>()?,
         })
     }
 
+    /// Run a test case or scenario, with the variables captured so far
+    fn run_testcase(&self, mut testcase: TestCase, vars: &mut Vars) -> Result<TestResult> {
+        if !testcase.steps.is_empty() {
+            return self.output_from_steps(testcase, vars);
+        }
+        testcase.map_text(|text| vars::substitute(text, vars));
+
+        let capture = std::mem::take(&mut testcase.capture);
+        let mut result = self.output_from_testcase(testcase)?;
+        match vars::capture(&capture, &result.actual_stdout) {
+            Ok(captured) => vars.extend(captured),
+            Err(failure) => result.capture_failure = Some(failure),
+        }
+        Ok(result)
+    }
+
     fn output_from_testcase(&self, testcase: TestCase) -> Result<TestResult> {
-        let output = self.executor.execute(&testcase)?;
-        Ok(TestResult::from_output(testcase, output))
+        let execution = match self.executor.execute(&testcase) {
+            Ok(execution) => execution,
+            Err(error) => return Ok(TestResult::from_error(testcase, error)),
+        };
+        let mut result = TestResult::from_output(testcase, execution.output);
+        result.interact_failure = execution.interact_failure;
+        result.limit_exceeded = execution.limit_exceeded;
+        result.usage = execution.usage;
+        result.termination_time = execution.termination_time;
+        result.http_requests = execution
+            .http_requests
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        result.http_failure = execution.http_failure;
+        result.calls = execution.calls.iter().map(ToString::to_string).collect();
+        result.calls_failure = execution.calls_failure;
+        Ok(result)
+    }
+
+    /// Run test cases one after the other in a single shell
+    ///
+    /// As in cram, the commands are joined in a single script, which prints a marker after each
+    /// command on stdout, along with the status of the command, and on stderr. The outputs of the
+    /// script are then split back between the test cases. The shell, environment and limits are
+    /// the ones of the first test case.
+    fn run_in_shared_shell(
+        &self,
+        tests: Vec<TestCase>,
+        shared_shell: &SharedShell,
+    ) -> Result<Vec<TestResult>> {
+        let first = match tests.first() {
+            Some(first) => first,
+            None => return Ok(vec![]),
+        };
+        let marker = format!("PHARAOH-SHARED-SHELL-{}", process::id());
+        let mut script = format!("{}\n", shared_shell.setup);
+        if shared_shell.merge_stderr {
+            script.push_str("exec 2>&1\n");
+        }
+        for (index, testcase) in tests.iter().enumerate() {
+            let cmd = match &testcase.cmd {
+                Cmd::Shell(line) => line.clone(),
+                Cmd::Argv(argv) => shell_words(argv),
+            };
+            script.push_str(&format!(
+                "{}\nprintf '\\n%s %s %s\\n' {} {} \"$?\"\n",
+                cmd, marker, index
+            ));
+            if !shared_shell.merge_stderr {
+                script.push_str(&format!(
+                    "printf '\\n%s %s 0\\n' {} {} >&2\n",
+                    marker, index
+                ));
+            }
+        }
+
+        let tempdir = tempfile::tempdir()?;
+        let session = TestCase {
+            name: first.name.clone(),
+            script: Some(script),
+            shell: first.shell.clone(),
+            env: first.env.clone(),
+            limits: first.limits,
+            workdir: Some(tempdir.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let execution = match self.executor.execute(&session) {
+            Ok(execution) => execution,
+            Err(error) => {
+                return Ok(tests
+                    .into_iter()
+                    .map(|testcase| TestResult::from_error(testcase, anyhow!("{:#}", error)))
+                    .collect())
+            }
+        };
+
+        let mut stdout = SharedOutput::new(&execution.output.stdout, &marker);
+        let mut stderr = SharedOutput::new(&execution.output.stderr, &marker);
+        let mut exited = false;
+        Ok(tests
+            .into_iter()
+            .enumerate()
+            .map(|(index, testcase)| {
+                if exited {
+                    return TestResult::from_error(
+                        testcase,
+                        anyhow!("the shared shell exited before running the test"),
+                    );
+                }
+                let (actual_stdout, status) = match stdout.next(index) {
+                    Some((output, status)) => (output, ExitStatus::from_raw(status << 8)),
+                    None => {
+                        // The shell exited in this test, which gets the rest of the output
+                        exited = true;
+                        (stdout.rest(), execution.output.status)
+                    }
+                };
+                let actual_stderr = match exited {
+                    true => stderr.rest(),
+                    false => stderr
+                        .next(index)
+                        .map(|(output, _)| output)
+                        .unwrap_or_default(),
+                };
+                let mut result = TestResult::from_output(
+                    testcase,
+                    Output {
+                        status,
+                        stdout: actual_stdout,
+                        stderr: actual_stderr,
+                    },
+                );
+                if exited {
+                    result.limit_exceeded = execution.limit_exceeded.clone();
+                }
+                result
+            })
+            .collect())
     }
+
+    /// Run the steps of a scenario in order, stopping at the first one that fails
+    ///
+    /// Unless the scenario has its own `workdir`, its steps share a new temporary directory. The
+    /// parser rejects the fields of the scenario that only apply to a single command, and each
+    /// step gets the mock HTTP server of the suite, if any.
+    fn output_from_steps(&self, mut testcase: TestCase, vars: &mut Vars) -> Result<TestResult> {
+        let tempdir = match testcase.workdir {
+            Some(_) => None,
+            None => Some(tempfile::tempdir()?),
+        };
+        let workdir = tempdir
+            .as_ref()
+            .map(|tempdir| tempdir.path().to_string_lossy().into_owned())
+            .or(testcase.workdir);
+
+        let steps = std::mem::take(&mut testcase.steps);
+        let mut result = TestResult::default();
+        for (index, step) in steps.into_iter().enumerate() {
+            let description = format!("step {} ({})", index + 1, step);
+            let step = TestCase {
+                name: testcase.name.clone(),
+                cmd: step.cmd,
+                script: step.script,
+                shell: testcase.shell.clone(),
+                env: testcase.env.clone(),
+                stdin: step.stdin,
+                stdout: step.stdout,
+                stderr: step.stderr,
+                output_match: testcase.output_match,
+                status: step.status,
+                limits: testcase.limits,
+                mock_http: testcase.mock_http.clone(),
+                stubs: testcase.stubs.clone(),
+                workdir: workdir.clone(),
+                capture: step.capture,
+                ..Default::default()
+            };
+            result = self.run_testcase(step, vars)?;
+            if !result.is_successful() {
+                result.failed_step = Some(description);
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The output of a shared shell, split at the markers printed after each command
+struct SharedOutput<'a> {
+    output: &'a [u8],
+    marker: Vec<u8>,
+}
+
+impl<'a> SharedOutput<'a> {
+    fn new(output: &'a [u8], marker: &str) -> Self {
+        SharedOutput {
+            output,
+            marker: format!("\n{} ", marker).into_bytes(),
+        }
+    }
+
+    /// The output of the command at `index` and its status, if its marker was printed
+    fn next(&mut self, index: usize) -> Option<(Vec<u8>, i32)> {
+        let marker = [self.marker.as_slice(), format!("{} ", index).as_bytes()].concat();
+        let start = self
+            .output
+            .windows(marker.len())
+            .position(|window| window == marker.as_slice())?;
+        let rest = &self.output[start + marker.len()..];
+        let end = rest.iter().position(|&byte| byte == b'\n')?;
+        let status = String::from_utf8_lossy(&rest[..end]).parse().ok()?;
+
+        let output = self.output[..start].to_vec();
+        self.output = &rest[end + 1..];
+        Some((output, status))
+    }
+
+    /// The output after the last marker
+    fn rest(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output).to_vec()
+    }
+}
+
+/// Join arguments into a shell command line, quoting them
+fn shell_words(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[cfg(test)]
@@ -67,9 +341,10 @@ mod tests {
     use pretty_assertions::assert_eq;
     use std::cell::RefCell;
     use std::collections::VecDeque;
-    use std::os::unix::process::ExitStatusExt;
-    use std::process::ExitStatus;
-    use std::process::Output;
+
+    use crate::types::output_match::OutputMatch;
+    use crate::types::testcase::{Readiness, Service, Step};
+    use executor::Execution;
 
     struct DummyExecutor {
         outputs: RefCell<VecDeque<Result<Output>>>,
@@ -84,8 +359,12 @@ mod tests {
     }
 
     impl Executor for DummyExecutor {
-        fn execute(&self, _testcase: &TestCase) -> Result<Output> {
-            self.outputs.borrow_mut().pop_front().unwrap()
+        fn execute(&self, _testcase: &TestCase) -> Result<Execution> {
+            self.outputs
+                .borrow_mut()
+                .pop_front()
+                .unwrap()
+                .map(Execution::from)
         }
     }
 
@@ -118,32 +397,39 @@ mod tests {
                     tests: vec![
                         TestCase {
                             name: "mytest".to_string(),
-                            cmd: "printf 'foo\n'".to_string(),
+                            cmd: "printf 'foo\n'".into(),
                             stdin: "".to_string(),
                             stdout: "foo\n".to_string(),
                             stderr: "".to_string(),
                             status: 0,
+                            ..Default::default()
                         },
                         TestCase {
                             name: "anothertest".to_string(),
-                            cmd: "printf 'bar\n'".to_string(),
+                            cmd: "printf 'bar\n'".into(),
                             stdin: "".to_string(),
                             stdout: "bar\n".to_string(),
                             stderr: "".to_string(),
                             status: 0,
+                            ..Default::default()
                         },
                     ],
+                    services: vec![],
+                    shared_shell: None,
                 },
                 TestSuite {
                     name: "anothersuite".to_string(),
                     tests: vec![TestCase {
                         name: "yetanothertest".to_string(),
-                        cmd: "printf 'baz\n'".to_string(),
+                        cmd: "printf 'baz\n'".into(),
                         stdin: "".to_string(),
                         stdout: "baz\n".to_string(),
                         stderr: "".to_string(),
                         status: 0,
+                        ..Default::default()
                     }],
+                    services: vec![],
+                    shared_shell: None,
                 },
             ],
         };
@@ -161,19 +447,51 @@ mod tests {
                                 name: "mytest".to_string(),
                                 expected_stdout: "foo\n".to_string(),
                                 actual_stdout: "foo\n".to_string(),
+                                output_match: OutputMatch::Exact,
                                 expected_stderr: "".to_string(),
                                 actual_stderr: "".to_string(),
                                 expected_status: 0,
-                                actual_status: 0
+                                actual_status: 0,
+                                interact_failure: None,
+                                error: None,
+                                limit_exceeded: None,
+                                terminate_within: None,
+                                termination_time: None,
+                                max_duration: None,
+                                max_memory: None,
+                                usage: Default::default(),
+                                service_logs: vec![],
+                                http_requests: vec![],
+                                http_failure: None,
+                                calls: vec![],
+                                calls_failure: None,
+                                failed_step: None,
+                                capture_failure: None,
                             },
                             TestResult {
                                 name: "anothertest".to_string(),
                                 expected_stdout: "bar\n".to_string(),
                                 actual_stdout: "bar\n".to_string(),
+                                output_match: OutputMatch::Exact,
                                 expected_stderr: "".to_string(),
                                 actual_stderr: "".to_string(),
                                 expected_status: 0,
-                                actual_status: 0
+                                actual_status: 0,
+                                interact_failure: None,
+                                error: None,
+                                limit_exceeded: None,
+                                terminate_within: None,
+                                termination_time: None,
+                                max_duration: None,
+                                max_memory: None,
+                                usage: Default::default(),
+                                service_logs: vec![],
+                                http_requests: vec![],
+                                http_failure: None,
+                                calls: vec![],
+                                calls_failure: None,
+                                failed_step: None,
+                                capture_failure: None,
                             },
                         ]
                     },
@@ -183,10 +501,26 @@ mod tests {
                             name: "yetanothertest".to_string(),
                             expected_stdout: "baz\n".to_string(),
                             actual_stdout: "baz\n".to_string(),
+                            output_match: OutputMatch::Exact,
                             expected_stderr: "".to_string(),
                             actual_stderr: "".to_string(),
                             expected_status: 0,
-                            actual_status: 0
+                            actual_status: 0,
+                            interact_failure: None,
+                            error: None,
+                            limit_exceeded: None,
+                            terminate_within: None,
+                            termination_time: None,
+                            max_duration: None,
+                            max_memory: None,
+                            usage: Default::default(),
+                            service_logs: vec![],
+                            http_requests: vec![],
+                            http_failure: None,
+                            calls: vec![],
+                            calls_failure: None,
+                            failed_step: None,
+                            capture_failure: None,
                         },]
                     },
                 ]
@@ -195,6 +529,253 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_run_all_tests_with_execution_error() {
+        // GIVEN
+        let executor = DummyExecutor::new(vec![Err(anyhow::anyhow!("program not found"))]);
+        let runner = DefaultRunner::with_executor(executor);
+
+        let collection = TestSuiteCollection {
+            testsuites: vec![TestSuite {
+                name: "mysuite".to_string(),
+                tests: vec![TestCase {
+                    name: "mytest".to_string(),
+                    cmd: "missing".into(),
+                    ..Default::default()
+                }],
+                services: vec![],
+                shared_shell: None,
+            }],
+        };
+
+        // WHEN
+        let result = runner.run_all_tests(collection).unwrap();
+
+        // THEN
+        assert_eq!(
+            Some("program not found".to_string()),
+            result.testsuites[0].results[0].error
+        );
+    }
+
+    #[test]
+    fn test_run_all_tests_with_broken_service() {
+        // GIVEN
+        let executor = DummyExecutor::new(vec![]);
+        let runner = DefaultRunner::with_executor(executor);
+
+        let collection = TestSuiteCollection {
+            testsuites: vec![TestSuite {
+                name: "mysuite".to_string(),
+                tests: vec![TestCase {
+                    name: "mytest".to_string(),
+                    cmd: "curl localhost:$API_PORT".into(),
+                    ..Default::default()
+                }],
+                services: vec![Service {
+                    name: "api".to_string(),
+                    cmd: "echo 'address in use'; exit 1".into(),
+                    ready: Readiness {
+                        port: true,
+                        ..Default::default()
+                    },
+                    timeout: std::time::Duration::from_secs(5),
+                    ..Default::default()
+                }],
+                shared_shell: None,
+            }],
+        };
+
+        // WHEN
+        let result = runner.run_all_tests(collection).unwrap();
+
+        // THEN
+        assert_eq!(
+            Some(
+                "service `api` exited before being ready, its logs were:\naddress in use\n"
+                    .to_string()
+            ),
+            result.testsuites[0].results[0].error
+        );
+    }
+
+    #[test]
+    fn test_run_all_tests_with_steps() {
+        // GIVEN
+        let executor = DummyExecutor::new(vec![
+            Ok(Output {
+                status: ExitStatus::from_raw(0 << 8),
+                stdout: "".as_bytes().to_vec(),
+                stderr: "".as_bytes().to_vec(),
+            }),
+            Ok(Output {
+                status: ExitStatus::from_raw(1 << 8),
+                stdout: "".as_bytes().to_vec(),
+                stderr: "nothing to commit\n".as_bytes().to_vec(),
+            }),
+        ]);
+        let runner = DefaultRunner::with_executor(executor);
+
+        let collection = TestSuiteCollection {
+            testsuites: vec![TestSuite {
+                name: "mysuite".to_string(),
+                tests: vec![TestCase {
+                    name: "mytest".to_string(),
+                    steps: vec![
+                        Step {
+                            cmd: "git init".into(),
+                            ..Default::default()
+                        },
+                        Step {
+                            name: Some("commit".to_string()),
+                            cmd: "git commit -m first".into(),
+                            ..Default::default()
+                        },
+                        Step {
+                            cmd: "git log".into(),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                }],
+                services: vec![],
+                shared_shell: None,
+            }],
+        };
+
+        // WHEN
+        let result = runner.run_all_tests(collection).unwrap();
+
+        // THEN
+        let result = &result.testsuites[0].results[0];
+        assert_eq!("mytest", result.name);
+        assert_eq!(Some("step 2 (commit)".to_string()), result.failed_step);
+        assert_eq!("nothing to commit\n", result.actual_stderr);
+    }
+
+    #[test]
+    fn test_run_all_tests_with_captures() {
+        // GIVEN
+        let executor = DummyExecutor::new(vec![
+            Ok(Output {
+                status: ExitStatus::from_raw(0 << 8),
+                stdout: "created user 42\n".as_bytes().to_vec(),
+                stderr: "".as_bytes().to_vec(),
+            }),
+            Ok(Output {
+                status: ExitStatus::from_raw(0 << 8),
+                stdout: "deleted user 42\n".as_bytes().to_vec(),
+                stderr: "".as_bytes().to_vec(),
+            }),
+            Ok(Output {
+                status: ExitStatus::from_raw(0 << 8),
+                stdout: "".as_bytes().to_vec(),
+                stderr: "".as_bytes().to_vec(),
+            }),
+        ]);
+        let runner = DefaultRunner::with_executor(executor);
+
+        let collection = TestSuiteCollection {
+            testsuites: vec![TestSuite {
+                name: "mysuite".to_string(),
+                tests: vec![
+                    TestCase {
+                        name: "create".to_string(),
+                        stdout: "created user 42\n".to_string(),
+                        capture: [("id".to_string(), r"user (\d+)".to_string())].into(),
+                        ..Default::default()
+                    },
+                    TestCase {
+                        name: "delete".to_string(),
+                        stdout: "deleted user ${id}\n".to_string(),
+                        ..Default::default()
+                    },
+                    TestCase {
+                        name: "list".to_string(),
+                        capture: [("first".to_string(), r"\w+".to_string())].into(),
+                        ..Default::default()
+                    },
+                ],
+                services: vec![],
+                shared_shell: None,
+            }],
+        };
+
+        // WHEN
+        let result = runner.run_all_tests(collection).unwrap();
+
+        // THEN
+        let results = &result.testsuites[0].results;
+        assert!(results[0].is_successful());
+        assert_eq!("deleted user 42\n", results[1].expected_stdout);
+        assert!(results[1].is_successful());
+        assert_eq!(
+            Some(r#"`first` did not match "\\w+""#.to_string()),
+            results[2].capture_failure
+        );
+    }
+
+    #[test]
+    fn test_run_all_tests_in_shared_shell() {
+        // GIVEN
+        let marker = format!("PHARAOH-SHARED-SHELL-{}", process::id());
+        let executor = DummyExecutor::new(vec![Ok(Output {
+            status: ExitStatus::from_raw(2 << 8),
+            stdout: format!("foo\n\n{m} 0 0\nbar\n{m} 1 3\nbaz\n", m = marker).into_bytes(),
+            stderr: format!("\n{m} 0 0\noops\n\n{m} 1 0\n", m = marker).into_bytes(),
+        })]);
+        let runner = DefaultRunner::with_executor(executor);
+        let testcase = |name: &str| TestCase {
+            name: name.to_string(),
+            cmd: "true".into(),
+            ..Default::default()
+        };
+
+        let collection = TestSuiteCollection {
+            testsuites: vec![TestSuite {
+                name: "mysuite".to_string(),
+                tests: vec![
+                    testcase("first"),
+                    testcase("second"),
+                    testcase("third"),
+                    testcase("fourth"),
+                ],
+                services: vec![],
+                shared_shell: Some(SharedShell::default()),
+            }],
+        };
+
+        // WHEN
+        let result = runner.run_all_tests(collection).unwrap();
+
+        // THEN
+        let results = &result.testsuites[0].results;
+        let outputs = results
+            .iter()
+            .map(|result| {
+                (
+                    result.actual_stdout.as_str(),
+                    result.actual_stderr.as_str(),
+                    result.actual_status,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("foo\n", "", 0),
+                ("bar", "oops\n", 3),
+                ("baz\n", "", 2),
+                ("", "", 0)
+            ],
+            outputs
+        );
+        assert_eq!(None, results[2].error);
+        assert_eq!(
+            Some("the shared shell exited before running the test".to_string()),
+            results[3].error
+        );
+    }
+
     #[test]
     fn test_new_calls_with_executor() {
         // GIVEN
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, bail, Context, Result};
+use regex::bytes::Regex;
+use std::collections::BTreeMap;
+use std::env;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::executor::build_command;
+use super::session::Session;
+use crate::types::testcase::{Service, TestCase};
+
+/// How often the readiness of a service is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The [Service]s of a suite, stopped when dropped
+pub struct Services {
+    running: Vec<Running>,
+}
+
+struct Running {
+    name: String,
+    port_variable: String,
+    port: u16,
+    session: Session,
+    logged: usize,
+}
+
+impl Services {
+    /// Start every service in order, and wait for each of them to be ready
+    ///
+    /// A service sees the ports of the services started before it. On error, the services
+    /// already started are stopped.
+    pub fn start(services: &[Service]) -> Result<Self> {
+        let mut started = Services { running: vec![] };
+        for service in services {
+            let running = started.start_one(service)?;
+            started.running.push(running);
+        }
+        Ok(started)
+    }
+
+    fn start_one(&self, service: &Service) -> Result<Running> {
+        let port = free_port().context("failed to allocate a port")?;
+        let mut command = build_command(
+            &service.cmd,
+            &service.shell.clone().unwrap_or_default(),
+            env::var_os("PATH").as_deref(),
+        )?;
+        command.envs(self.env()).env("PORT", port.to_string());
+        let session = Session::spawn(&mut command)
+            .with_context(|| format!("failed to start service `{}`", service.name))?;
+
+        let mut running = Running {
+            name: service.name.clone(),
+            port_variable: service.port_variable(),
+            port,
+            session,
+            logged: 0,
+        };
+        if let Err(reason) = running.wait_until_ready(service) {
+            let logs = running.session.transcript();
+            running.stop();
+            bail!(
+                "service `{}` {}, its logs were:\n{}",
+                service.name,
+                reason,
+                logs
+            );
+        }
+        Ok(running)
+    }
+
+    /// The environment variables exported to the test cases
+    pub fn env(&self) -> BTreeMap<String, String> {
+        self.running
+            .iter()
+            .map(|running| (running.port_variable.clone(), running.port.to_string()))
+            .collect()
+    }
+
+    /// Give `testcase` the environment of the services, without overriding its own variables
+    pub fn prepare(&self, testcase: &mut TestCase) {
+        for (name, value) in self.env() {
+            testcase.env.entry(name).or_insert(value);
+        }
+    }
+
+    /// What each service logged since the previous call, skipping the silent ones
+    pub fn logs(&mut self) -> Vec<(String, String)> {
+        self.running
+            .iter_mut()
+            .filter_map(|running| {
+                let transcript = running.session.transcript();
+                let logs = transcript
+                    .get(running.logged..)
+                    .unwrap_or_default()
+                    .to_string();
+                running.logged = transcript.len();
+                match logs.is_empty() {
+                    true => None,
+                    false => Some((running.name.clone(), logs)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for Services {
+    fn drop(&mut self) {
+        for running in self.running.drain(..) {
+            running.stop();
+        }
+    }
+}
+
+impl Running {
+    fn wait_until_ready(&mut self, service: &Service) -> Result<(), String> {
+        let stdout = match &service.ready.stdout {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|error| error.to_string())?),
+            None => None,
+        };
+        let deadline = Instant::now() + service.timeout;
+        loop {
+            let ready = (!service.ready.port || self.accepts_connections())
+                && stdout
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.is_match(self.session.stdout()))
+                && service
+                    .ready
+                    .file
+                    .as_ref()
+                    .is_none_or(|file| Path::new(file).exists());
+            if ready {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("is not ready after {:?}", service.timeout));
+            }
+            if !self.session.pump(POLL_INTERVAL) {
+                return Err("exited before being ready".to_string());
+            }
+        }
+    }
+
+    fn accepts_connections(&self) -> bool {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, self.port));
+        TcpStream::connect_timeout(&address, POLL_INTERVAL).is_ok()
+    }
+
+    fn stop(mut self) {
+        self.session.kill();
+        // The service was killed, how it exited does not matter
+        let _ = self.session.wait();
+    }
+}
+
+/// Find a TCP port that nothing listens on
+fn free_port() -> Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    match port {
+        0 => Err(anyhow!("no port was allocated")),
+        port => Ok(port),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::testcase::Readiness;
+
+    fn service(name: &str, cmd: &str, ready: Readiness) -> Service {
+        Service {
+            name: name.to_string(),
+            cmd: cmd.into(),
+            ready,
+            timeout: Duration::from_secs(5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_start_ready_on_stdout() {
+        // GIVEN
+        let services = vec![service(
+            "echo server",
+            "echo \"listening on $PORT\"; sleep 10",
+            Readiness {
+                stdout: Some("^listening".to_string()),
+                ..Default::default()
+            },
+        )];
+
+        // WHEN
+        let mut services = Services::start(&services).unwrap();
+
+        // THEN
+        let port = services.env()["ECHO_SERVER_PORT"].clone();
+        assert_eq!(
+            vec![(
+                "echo server".to_string(),
+                format!("listening on {}\n", port)
+            )],
+            services.logs()
+        );
+        assert_eq!(Vec::<(String, String)>::new(), services.logs());
+    }
+
+    #[test]
+    fn test_start_ready_on_port() {
+        // GIVEN
+        let services = vec![service(
+            "http",
+            "exec python3 -m http.server --bind 127.0.0.1 $PORT",
+            Readiness {
+                port: true,
+                ..Default::default()
+            },
+        )];
+
+        // WHEN
+        let services = Services::start(&services).unwrap();
+
+        // THEN
+        let port: u16 = services.env()["HTTP_PORT"].parse().unwrap();
+        assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_ok());
+    }
+
+    #[test]
+    fn test_start_ready_on_file() {
+        // GIVEN
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("ready");
+        let services = vec![service(
+            "writer",
+            &format!("sleep 0.1; touch '{}'; sleep 10", file.display()),
+            Readiness {
+                file: Some(file.display().to_string()),
+                ..Default::default()
+            },
+        )];
+
+        // WHEN
+        let result = Services::start(&services);
+
+        // THEN
+        assert!(result.is_ok());
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_start_exited() {
+        // GIVEN
+        let services = vec![service(
+            "broken",
+            "echo 'cannot bind' >&2; exit 1",
+            Readiness {
+                port: true,
+                ..Default::default()
+            },
+        )];
+
+        // WHEN
+        let result = Services::start(&services);
+
+        // THEN
+        assert_eq!(
+            "service `broken` exited before being ready, its logs were:\ncannot bind\n",
+            result.err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_start_timeout() {
+        // GIVEN
+        let mut slow = service(
+            "slow",
+            "sleep 10",
+            Readiness {
+                stdout: Some("never".to_string()),
+                ..Default::default()
+            },
+        );
+        slow.timeout = Duration::from_millis(200);
+
+        // WHEN
+        let started = Instant::now();
+        let result = Services::start(&[slow]);
+
+        // THEN
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            "service `slow` is not ready after 200ms, its logs were:\n",
+            result.err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_prepare_keeps_testcase_env() {
+        // GIVEN
+        let services =
+            Services::start(&[service("api", "sleep 10", Readiness::default())]).unwrap();
+        let mut testcase = TestCase {
+            env: [("API_PORT".to_string(), "1234".to_string())].into(),
+            ..Default::default()
+        };
+        let mut other = TestCase::default();
+
+        // WHEN
+        services.prepare(&mut testcase);
+        services.prepare(&mut other);
+
+        // THEN
+        assert_eq!("1234", testcase.env["API_PORT"]);
+        assert_eq!(services.env()["API_PORT"], other.env["API_PORT"]);
+    }
+}
@@ -0,0 +1,417 @@
+use anyhow::Result;
+use regex::bytes::Regex;
+use std::io::{self, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::types::result::Usage;
+use crate::types::signal::Signal;
+
+/// How often [Session::exit_within] checks whether the child has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A running child process whose output is continuously collected
+pub struct Session {
+    child: Child,
+    started: Instant,
+    stdin: Option<ChildStdin>,
+    chunks: Receiver<(Stream, Vec<u8>)>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    received: Vec<u8>,
+    matched: usize,
+    stdout_matched: usize,
+    transcript: Vec<u8>,
+    last_signal: Option<Instant>,
+}
+
+impl Session {
+    pub fn spawn(command: &mut Command) -> Result<Self> {
+        // The child gets its own process group, so that it can be killed along with its children
+        let started = Instant::now();
+        let mut child = command
+            .process_group(0)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, chunks) = mpsc::channel();
+        forward(child.stdout.take().unwrap(), Stream::Stdout, sender.clone());
+        forward(child.stderr.take().unwrap(), Stream::Stderr, sender);
+
+        Ok(Session {
+            stdin: child.stdin.take(),
+            child,
+            started,
+            chunks,
+            stdout: vec![],
+            stderr: vec![],
+            received: vec![],
+            matched: 0,
+            stdout_matched: 0,
+            transcript: vec![],
+            last_signal: None,
+        })
+    }
+
+    /// Write some input to the child
+    pub fn send(&mut self, input: &str) -> io::Result<()> {
+        self.transcript.extend_from_slice(input.as_bytes());
+        match self.stdin.as_mut() {
+            Some(stdin) => {
+                stdin.write_all(input.as_bytes())?;
+                stdin.flush()
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "stdin is already closed",
+            )),
+        }
+    }
+
+    /// Write the whole input to the child, then close its stdin
+    ///
+    /// A child that exits without reading all of its input is not an error.
+    pub fn feed(&mut self, input: &str) -> Result<()> {
+        if let Err(error) = self.send(input) {
+            if error.kind() != io::ErrorKind::BrokenPipe {
+                return Err(error.into());
+            }
+        }
+        self.close_stdin();
+        Ok(())
+    }
+
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+
+    pub fn signal(&mut self, signal: Signal) -> io::Result<()> {
+        match unsafe { libc::kill(self.child.id() as libc::pid_t, signal.0) } {
+            0 => {
+                self.last_signal = Some(Instant::now());
+                Ok(())
+            }
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// When the last signal was successfully sent, if any
+    pub fn last_signal(&self) -> Option<Instant> {
+        self.last_signal
+    }
+
+    pub fn kill(&mut self) {
+        // The group may already be gone, in which case there is nothing to do
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+
+    /// Wait until the output received since the last match matches `pattern`
+    ///
+    /// On failure, the reason is returned as an error.
+    pub fn expect(&mut self, pattern: &Regex, timeout: Duration) -> Result<(), String> {
+        self.wait_for(timeout, |session| {
+            match pattern.find(&session.received[session.matched..]) {
+                Some(found) => {
+                    session.matched += found.end();
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Same as [Session::expect], looking only at stdout
+    pub fn expect_stdout(&mut self, pattern: &Regex, timeout: Duration) -> Result<(), String> {
+        self.wait_for(timeout, |session| {
+            match pattern.find(&session.stdout[session.stdout_matched..]) {
+                Some(found) => {
+                    session.stdout_matched += found.end();
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    fn wait_for(
+        &mut self,
+        timeout: Duration,
+        mut found: impl FnMut(&mut Self) -> bool,
+    ) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if found(self) {
+                return Ok(());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.chunks.recv_timeout(remaining) {
+                Ok(chunk) => self.receive(chunk),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("timed out after {:?}", timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("the command closed its output".to_string())
+                }
+            }
+        }
+    }
+
+    /// Collect the output received within `timeout`
+    ///
+    /// Returns `false` once the child has closed its output.
+    pub fn pump(&mut self, timeout: Duration) -> bool {
+        match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => self.receive(chunk),
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+        while let Ok(chunk) = self.chunks.try_recv() {
+            self.receive(chunk);
+        }
+        true
+    }
+
+    /// Everything received on stdout so far
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Everything that was sent to and received from the child so far
+    pub fn transcript(&mut self) -> String {
+        while let Ok(chunk) = self.chunks.try_recv() {
+            self.receive(chunk);
+        }
+        String::from_utf8_lossy(&self.transcript).into_owned()
+    }
+
+    /// Wait for the child to exit at most `max` after `since`, or kill its process group then
+    ///
+    /// Returns the time from `since` until the child exited, or until it was killed, which is
+    /// then more than `max`. The child is left for [Session::wait] to reap.
+    pub fn exit_within(&mut self, since: Instant, max: Duration) -> io::Result<Duration> {
+        self.close_stdin();
+        loop {
+            if self.has_exited()? {
+                return Ok(since.elapsed());
+            }
+            let elapsed = since.elapsed();
+            if elapsed > max {
+                self.kill();
+                return Ok(elapsed);
+            }
+            let timeout = (max - elapsed).min(POLL_INTERVAL);
+            if !self.pump(timeout) {
+                thread::sleep(timeout);
+            }
+        }
+    }
+
+    /// Whether the child has exited, without reaping it
+    fn has_exited(&self) -> io::Result<bool> {
+        // SAFETY: siginfo_t is a plain C struct, for which all zeroes is a valid value
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+        loop {
+            match unsafe { libc::waitid(libc::P_PID, self.child.id(), &mut info, options) } {
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                -1 => return Err(io::Error::last_os_error()),
+                // SAFETY: waitid filled in the pid of the child if it has exited, or left it zero
+                _ => return Ok(unsafe { info.si_pid() } != 0),
+            }
+        }
+    }
+
+    /// Close the stdin of the child, and wait for it to exit
+    pub fn wait(mut self) -> Result<(Output, Usage)> {
+        self.close_stdin();
+        while let Ok(chunk) = self.chunks.recv() {
+            self.receive(chunk);
+        }
+        let (status, rusage) = wait4(self.child.id() as libc::pid_t)?;
+
+        let usage = Usage {
+            wall_time: self.started.elapsed(),
+            user_time: to_duration(rusage.ru_utime),
+            system_time: to_duration(rusage.ru_stime),
+            max_rss: max_rss_in_bytes(rusage.ru_maxrss),
+        };
+        let output = Output {
+            status,
+            stdout: self.stdout,
+            stderr: self.stderr,
+        };
+        Ok((output, usage))
+    }
+
+    fn receive(&mut self, (stream, data): (Stream, Vec<u8>)) {
+        match stream {
+            Stream::Stdout => self.stdout.extend_from_slice(&data),
+            Stream::Stderr => self.stderr.extend_from_slice(&data),
+        }
+        self.received.extend_from_slice(&data);
+        self.transcript.extend_from_slice(&data);
+    }
+}
+
+/// Wait for a child to exit, collecting the resources used by it and its own children
+fn wait4(pid: libc::pid_t) -> io::Result<(ExitStatus, libc::rusage)> {
+    let mut status = 0;
+    // SAFETY: rusage is a plain C struct, for which all zeroes is a valid value
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        match unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return Err(io::Error::last_os_error()),
+            _ => return Ok((ExitStatus::from_raw(status), rusage)),
+        }
+    }
+}
+
+fn to_duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+#[cfg(target_os = "macos")]
+fn max_rss_in_bytes(max_rss: libc::c_long) -> u64 {
+    max_rss as u64
+}
+
+#[cfg(not(target_os = "macos"))]
+fn max_rss_in_bytes(max_rss: libc::c_long) -> u64 {
+    max_rss as u64 * 1024
+}
+
+fn forward(
+    mut reader: impl Read + Send + 'static,
+    stream: Stream,
+    sender: Sender<(Stream, Vec<u8>)>,
+) {
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    if sender.send((stream, buffer[..read].to_vec())).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn a_session(script: &str) -> Session {
+        Session::spawn(Command::new("/bin/sh").args(vec!["-c", script])).unwrap()
+    }
+
+    #[test]
+    fn test_feed_and_wait() {
+        // GIVEN
+        let mut session = a_session("cat; echo oops >&2");
+
+        // WHEN
+        session.feed("hello\n").unwrap();
+        let (output, usage) = session.wait().unwrap();
+
+        // THEN
+        assert_eq!(b"hello\n".to_vec(), output.stdout);
+        assert_eq!(b"oops\n".to_vec(), output.stderr);
+        assert!(output.status.success());
+        assert!(usage.max_rss > 0);
+    }
+
+    #[test]
+    fn test_wait_measures_time() {
+        // GIVEN
+        let session = a_session("sleep 0.2");
+
+        // WHEN
+        let (_, usage) = session.wait().unwrap();
+
+        // THEN
+        assert!(usage.wall_time >= Duration::from_millis(200));
+        assert!(usage.user_time + usage.system_time < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_exit_within() {
+        // GIVEN
+        let mut exits = a_session("sleep 0.1");
+        let mut hangs = a_session("sleep 5");
+        let since = Instant::now();
+
+        // WHEN
+        let exited = exits.exit_within(since, Duration::from_secs(5)).unwrap();
+        let killed = hangs
+            .exit_within(since, Duration::from_millis(200))
+            .unwrap();
+        let (exited_output, _) = exits.wait().unwrap();
+        let (killed_output, _) = hangs.wait().unwrap();
+
+        // THEN
+        assert!(exited < Duration::from_secs(1));
+        assert!(exited_output.status.success());
+        assert!(killed > Duration::from_millis(200) && killed < Duration::from_secs(1));
+        assert_eq!(Some(libc::SIGKILL), killed_output.status.signal());
+    }
+
+    #[test]
+    fn test_expect_then_send() {
+        // GIVEN
+        let mut session = a_session("printf '> '; read line; echo \"got $line\"");
+
+        // WHEN
+        let prompt = session.expect(&Regex::new("> ").unwrap(), Duration::from_secs(5));
+        session.send("foo\n").unwrap();
+        let answer = session.expect(&Regex::new("got foo").unwrap(), Duration::from_secs(5));
+
+        // THEN
+        assert_eq!(Ok(()), prompt);
+        assert_eq!(Ok(()), answer);
+        assert_eq!("> foo\ngot foo\n", session.transcript());
+    }
+
+    #[test]
+    fn test_expect_stdout_ignores_stderr() {
+        // GIVEN
+        let mut session = a_session("echo ready >&2; sleep 0.1; echo ready");
+
+        // WHEN
+        let result = session.expect_stdout(&Regex::new("ready").unwrap(), Duration::from_secs(5));
+
+        // THEN
+        assert_eq!(Ok(()), result);
+        assert_eq!("ready\nready\n", session.transcript());
+    }
+
+    #[test]
+    fn test_expect_timeout() {
+        // GIVEN
+        let mut session = a_session("sleep 5");
+
+        // WHEN
+        let result = session.expect(&Regex::new("never").unwrap(), Duration::from_millis(50));
+        session.kill();
+
+        // THEN
+        assert_eq!(Err("timed out after 50ms".to_string()), result);
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::iter;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+use crate::types::testcase::{ExpectedCall, Stub};
+
+/// A call received by a stub
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Call {
+    pub argv: Vec<String>,
+    /// The stdin of the call, if the stub read it
+    pub stdin: Option<String>,
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", quote_argv(&self.argv))
+    }
+}
+
+/// A directory of fake programs, removed when dropped
+///
+/// Each stub is a shell script that records its call in a numbered directory before printing its
+/// canned output, so that the calls can be read back in order.
+pub struct Stubs {
+    dir: TempDir,
+}
+
+impl Stubs {
+    /// Write the stubs, the ones in `read_stdin` recording their stdin too
+    pub fn create(stubs: &BTreeMap<String, Stub>, read_stdin: &[&str]) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("bin"))?;
+        fs::create_dir(dir.path().join("calls"))?;
+        let stubs_dir = Stubs { dir };
+        for (name, stub) in stubs {
+            stubs_dir
+                .write_stub(name, stub, read_stdin.contains(&name.as_str()))
+                .with_context(|| format!("failed to create stub `{}`", name))?;
+        }
+        Ok(stubs_dir)
+    }
+
+    /// The directory to put first on `PATH`
+    pub fn bin(&self) -> PathBuf {
+        self.dir.path().join("bin")
+    }
+
+    /// The calls received so far, in order
+    pub fn calls(&self) -> Result<Vec<Call>> {
+        let calls = self.dir.path().join("calls");
+        let mut indexes = fs::read_dir(&calls)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        indexes.sort_unstable();
+
+        Ok(indexes
+            .into_iter()
+            .map(|index| {
+                let call = calls.join(index.to_string());
+                let argv = fs::read(call.join("argv")).unwrap_or_default();
+                let argv = String::from_utf8_lossy(&argv);
+                let stdin = fs::read(call.join("stdin")).ok();
+                Call {
+                    argv: argv
+                        .strip_suffix('\0')
+                        .unwrap_or(&argv)
+                        .split('\0')
+                        .map(str::to_string)
+                        .collect(),
+                    stdin: stdin.map(|stdin| String::from_utf8_lossy(&stdin).into_owned()),
+                }
+            })
+            .collect())
+    }
+
+    fn write_stub(&self, name: &str, stub: &Stub, read_stdin: bool) -> Result<()> {
+        let stdout = self.dir.path().join(format!("{}.stdout", name));
+        let stderr = self.dir.path().join(format!("{}.stderr", name));
+        fs::write(&stdout, &stub.stdout)?;
+        fs::write(&stderr, &stub.stderr)?;
+
+        let calls = quote(&self.dir.path().join("calls").to_string_lossy());
+        let mut script = format!(
+            "#!/bin/sh\n\
+             i=0\n\
+             while ! mkdir {calls}/$i 2>/dev/null; do\n\
+             \x20 [ -d {calls} ] || exit 127\n\
+             \x20 i=$((i + 1))\n\
+             done\n\
+             printf '%s\\0' {name} \"$@\" > {calls}/$i/argv\n",
+            calls = calls,
+            name = quote(name),
+        );
+        if read_stdin {
+            script.push_str(&format!("cat > {}/$i/stdin\n", calls));
+        }
+        script.push_str(&format!(
+            "cat {}\ncat {} >&2\nexit {}\n",
+            quote(&stdout.to_string_lossy()),
+            quote(&stderr.to_string_lossy()),
+            stub.status
+        ));
+
+        let path = self.bin().join(name);
+        fs::write(&path, script)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+}
+
+/// Compare the calls that were received to the expected ones
+///
+/// On mismatch, a description of the first difference is returned.
+pub fn check(expected: &[ExpectedCall], actual: &[Call]) -> Option<String> {
+    for (index, expected) in expected.iter().enumerate() {
+        let actual = match actual.get(index) {
+            Some(actual) => actual,
+            None => {
+                return Some(format!(
+                    "expected call {} to be `{}`, but it was not made",
+                    index + 1,
+                    quote_argv(&expected.argv)
+                ))
+            }
+        };
+        if expected.argv != actual.argv {
+            return Some(format!(
+                "expected call {} to be `{}`, actual `{}`",
+                index + 1,
+                quote_argv(&expected.argv),
+                actual
+            ));
+        }
+        if let Some(stdin) = expected
+            .stdin
+            .as_ref()
+            .filter(|stdin| Some(*stdin) != actual.stdin.as_ref())
+        {
+            return Some(format!(
+                "expected call {} (`{}`) to have stdin {:?}, actual {:?}",
+                index + 1,
+                actual,
+                stdin,
+                actual.stdin.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    actual
+        .get(expected.len())
+        .map(|unexpected| format!("unexpected call {}: `{}`", expected.len() + 1, unexpected))
+}
+
+/// The names of the stubs whose stdin is checked by `expected`
+pub fn reading_stdin(expected: &[ExpectedCall]) -> Vec<&str> {
+    expected
+        .iter()
+        .filter(|call| call.stdin.is_some())
+        .filter_map(|call| call.argv.first())
+        .map(String::as_str)
+        .collect()
+}
+
+/// Quote a word for the shell, if needed
+fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    match plain {
+        true => word.to_string(),
+        false => format!("'{}'", word.replace('\'', r"'\''")),
+    }
+}
+
+fn quote_argv(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Prepend the stubs to a `PATH`
+pub fn path(stubs: &Stubs, path: Option<&OsStr>) -> Result<OsString> {
+    let path = path.map(env::split_paths).into_iter().flatten();
+    Ok(env::join_paths(iter::once(stubs.bin()).chain(path))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_stubs_record_calls() {
+        // GIVEN
+        let stubs = Stubs::create(
+            &[(
+                "git".to_string(),
+                Stub {
+                    stdout: "pushed\n".to_string(),
+                    stderr: "warning\n".to_string(),
+                    status: 3,
+                },
+            )]
+            .into(),
+            &["git"],
+        )
+        .unwrap();
+
+        // WHEN
+        let output = Command::new(stubs.bin().join("git"))
+            .args(["push", "my 'remote'"])
+            .output()
+            .unwrap();
+
+        // THEN
+        assert_eq!(b"pushed\n".to_vec(), output.stdout);
+        assert_eq!(b"warning\n".to_vec(), output.stderr);
+        assert_eq!(Some(3), output.status.code());
+        assert_eq!(
+            vec![Call {
+                argv: vec![
+                    "git".to_string(),
+                    "push".to_string(),
+                    "my 'remote'".to_string()
+                ],
+                stdin: Some("".to_string()),
+            }],
+            stubs.calls().unwrap()
+        );
+        assert_eq!(
+            "git push 'my '\\''remote'\\'''",
+            stubs.calls().unwrap()[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        // GIVEN
+        let expected = vec![ExpectedCall {
+            argv: vec!["curl".to_string(), "-d@-".to_string()],
+            stdin: Some("data".to_string()),
+        }];
+        let call = |argv: &[&str], stdin: &str| Call {
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            stdin: Some(stdin.to_string()),
+        };
+
+        // WHEN
+        let matching = check(&expected, &[call(&["curl", "-d@-"], "data")]);
+        let missing = check(&expected, &[]);
+        let wrong_argv = check(&expected, &[call(&["curl", "-I"], "")]);
+        let wrong_stdin = check(&expected, &[call(&["curl", "-d@-"], "other")]);
+        let unexpected = check(&[], &[call(&["curl", "a b"], "")]);
+
+        // THEN
+        assert_eq!(None, matching);
+        assert_eq!(
+            Some("expected call 1 to be `curl -d@-`, but it was not made".to_string()),
+            missing
+        );
+        assert_eq!(
+            Some("expected call 1 to be `curl -d@-`, actual `curl -I`".to_string()),
+            wrong_argv
+        );
+        assert_eq!(
+            Some(
+                "expected call 1 (`curl -d@-`) to have stdin \"data\", actual \"other\""
+                    .to_string()
+            ),
+            wrong_stdin
+        );
+        assert_eq!(
+            Some("unexpected call 1: `curl 'a b'`".to_string()),
+            unexpected
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! # Durations
+//!
+//! Helpers to read durations from test files. A duration is either a plain number of seconds
+//! (`2`, `0.5`) or a number followed by a unit (`500ms`, `2s`, `1m`).
+//!
+//! The functions in this module are meant to be used with `#[serde(with = "...")]`, and
+//! `#[schemars(schema_with = "...::schema")]`.
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::time::Duration;
+
+/// Parse a duration such as `500ms`, `2s`, `1.5` or `1m`
+pub fn parse(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", text))?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        unit => return Err(format!("invalid duration unit `{}` in `{}`", unit, text)),
+    };
+    from_secs(seconds, text)
+}
+
+/// The [Duration] of a number of seconds, which must be neither negative nor too large
+fn from_secs(seconds: f64, text: impl std::fmt::Display) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|error| format!("invalid duration `{}`: {}", text, error))
+}
+
+/// A number of seconds, or a number followed by `ms`, `s` or `m`
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+#[schemars(rename = "Duration")]
+enum RawDuration {
+    Seconds(f64),
+    Text(String),
+}
+
+impl RawDuration {
+    fn into_duration<E: de::Error>(self) -> Result<Duration, E> {
+        match self {
+            RawDuration::Seconds(seconds) => from_secs(seconds, seconds),
+            RawDuration::Text(text) => parse(&text),
+        }
+        .map_err(E::custom)
+    }
+}
+
+/// Deserialize a [Duration]
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    RawDuration::deserialize(deserializer)?.into_duration()
+}
+
+/// Serialize a [Duration] as a number of seconds
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// The JSON Schema of a [Duration]
+pub fn schema(gen: &mut SchemaGenerator) -> Schema {
+    RawDuration::json_schema(gen)
+}
+
+/// Same as the parent module, for optional durations
+pub mod option {
+    use super::*;
+
+    /// Deserialize an optional [Duration]
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<RawDuration>::deserialize(deserializer)?
+            .map(RawDuration::into_duration)
+            .transpose()
+    }
+
+    /// Serialize an optional [Duration] as a number of seconds
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// The JSON Schema of an optional [Duration]
+    pub fn schema(gen: &mut SchemaGenerator) -> Schema {
+        Option::<RawDuration>::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Duration::from_millis(500)), parse("500ms"));
+        assert_eq!(Ok(Duration::from_secs(2)), parse("2s"));
+        assert_eq!(Ok(Duration::from_millis(1500)), parse("1.5"));
+        assert_eq!(Ok(Duration::from_secs(60)), parse("1m"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("soon").is_err());
+        assert!(parse("3h").is_err());
+        assert_eq!(
+            Err(
+                "invalid duration `99999999999999999999s`: cannot convert float seconds to \
+                 Duration: value is either too big or NaN"
+                    .to_string()
+            ),
+            parse("99999999999999999999s")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_invalid() {
+        // GIVEN
+        let documents = ["-1", ".inf", ".nan", "1e30"];
+
+        // WHEN
+        let errors = documents
+            .iter()
+            .map(|document| {
+                let deserializer = serde_yaml::Deserializer::from_str(document);
+                deserialize(deserializer).unwrap_err().to_string()
+            })
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_eq!(
+            vec![
+                "invalid duration `-1`: cannot convert float seconds to Duration: value is negative",
+                "invalid duration `inf`: cannot convert float seconds to Duration: value is either too big or NaN",
+                "invalid duration `NaN`: cannot convert float seconds to Duration: value is either too big or NaN",
+                "invalid duration `1000000000000000000000000000000`: cannot convert float seconds to Duration: value is either too big or NaN",
+            ],
+            errors
+        );
+    }
+}
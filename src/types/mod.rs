@@ -1,4 +1,10 @@
 //! Common types used throughout Pharaoh
 
+pub mod duration;
+pub mod output_match;
 pub mod result;
+pub mod schema;
+pub mod signal;
+pub mod size;
 pub mod testcase;
+pub mod vars;
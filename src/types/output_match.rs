@@ -0,0 +1,225 @@
+//! # Output matching
+//!
+//! How the actual output of a command is compared to the expected one. Besides an exact
+//! comparison, expected outputs can be written as in [cram](https://bitheap.org/cram/), where
+//! each line can end with an annotation:
+//!
+//! * ` (re)`: the line is a regex, which must match the whole actual line.
+//! * ` (glob)`: the line is a glob, where `*` matches any text and `?` any character.
+//! * ` (esc)`: the line has escape sequences, such as `\t` or `\x1b`.
+//! * ` (no-eol)`: the line is the last one, and has no trailing newline.
+//!
+//! They can also be written as in [trycmd](https://docs.rs/trycmd), where `[..]` matches any text
+//! within a line, and a `...` line any number of lines.
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How an actual output is compared to the expected one
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMatch {
+    /// The outputs must be the same
+    #[default]
+    Exact,
+    /// The expected lines can be regexes, globs or escaped, as in cram
+    Cram,
+    /// The expected lines can have `[..]` and `...` wildcards, as in trycmd
+    Trycmd,
+}
+
+impl OutputMatch {
+    /// Whether `actual` matches `expected`
+    pub fn matches(&self, expected: &str, actual: &str) -> bool {
+        match self {
+            OutputMatch::Exact => expected == actual,
+            OutputMatch::Cram => {
+                let expected = expected.lines().collect::<Vec<_>>();
+                let actual = cram_lines(actual);
+                expected.len() == actual.len()
+                    && expected
+                        .iter()
+                        .zip(&actual)
+                        .all(|(expected, actual)| cram_line_matches(expected, actual))
+            }
+            OutputMatch::Trycmd => {
+                let expected = expected.lines().collect::<Vec<_>>();
+                let actual = actual.lines().collect::<Vec<_>>();
+                // Whether the expected lines from `i` on match the actual lines from `j` on, for
+                // all the `j`, computed from the last expected line up
+                let mut matching = (0..=actual.len())
+                    .map(|j| j == actual.len())
+                    .collect::<Vec<_>>();
+                for line in expected.iter().rev() {
+                    let mut previous = matching.clone();
+                    for j in (0..=actual.len()).rev() {
+                        previous[j] = if *line == "..." {
+                            matching[j] || (j < actual.len() && previous[j + 1])
+                        } else {
+                            j < actual.len()
+                                && matching[j + 1]
+                                && trycmd_line_matches(line, actual[j])
+                        };
+                    }
+                    matching = previous;
+                }
+                matching[0]
+            }
+        }
+    }
+}
+
+/// The lines of an output, the last one marked with ` (no-eol)` if it has no trailing newline
+fn cram_lines(output: &str) -> Vec<String> {
+    let mut lines = output.lines().map(str::to_string).collect::<Vec<_>>();
+    if !output.is_empty() && !output.ends_with('\n') {
+        if let Some(last) = lines.last_mut() {
+            last.push_str(" (no-eol)");
+        }
+    }
+    lines
+}
+
+fn cram_line_matches(expected: &str, actual: &str) -> bool {
+    if expected == actual {
+        return true;
+    }
+    if let Some(pattern) = expected.strip_suffix(" (re)") {
+        return Regex::new(&format!("^(?:{})$", pattern)).is_ok_and(|regex| regex.is_match(actual));
+    }
+    if let Some(pattern) = expected.strip_suffix(" (glob)") {
+        return Regex::new(&glob_to_regex(pattern)).is_ok_and(|regex| regex.is_match(actual));
+    }
+    if let Some(escaped) = expected.strip_suffix(" (esc)") {
+        return unescape(escaped).is_some_and(|expected| expected == actual);
+    }
+    false
+}
+
+fn trycmd_line_matches(expected: &str, actual: &str) -> bool {
+    if !expected.contains("[..]") {
+        return expected == actual;
+    }
+    let parts = expected
+        .split("[..]")
+        .map(regex::escape)
+        .collect::<Vec<_>>();
+    Regex::new(&format!("^{}$", parts.join(".*"))).is_ok_and(|regex| regex.is_match(actual))
+}
+
+/// Translate a glob, where `*` and `?` can be escaped with a backslash, to an anchored regex
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' => match chars.next() {
+                Some(escaped) => regex.push_str(&regex::escape(&escaped.to_string())),
+                None => regex.push_str(r"\\"),
+            },
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Interpret the escape sequences of a line, or `None` if one is invalid
+fn unescape(escaped: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => text.push('\n'),
+            'r' => text.push('\r'),
+            't' => text.push('\t'),
+            '\\' => text.push('\\'),
+            '\'' => text.push('\''),
+            'x' => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                text.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            _ => return None,
+        }
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_exact() {
+        // WHEN
+        let same = OutputMatch::Exact.matches("foo\n", "foo\n");
+        let different = OutputMatch::Exact.matches("fo. (re)\n", "foo\n");
+
+        // THEN
+        assert!(same);
+        assert!(!different);
+    }
+
+    #[test]
+    fn test_matches_cram() {
+        // GIVEN
+        let expected = "plain\n\
+                        [0-9]+ files? (re)\n\
+                        /tmp/*/out-?.txt (glob)\n\
+                        \\x1b[1mbold\\t! (esc)\n\
+                        last (no-eol)\n";
+
+        // WHEN
+        let matching = OutputMatch::Cram.matches(
+            expected,
+            "plain\n12 files\n/tmp/abc/out-1.txt\n\x1b[1mbold\t!\nlast",
+        );
+        let wrong_regex = OutputMatch::Cram.matches(
+            expected,
+            "plain\n12 folders\n/tmp/abc/out-1.txt\n\x1b[1mbold\t!\nlast",
+        );
+        let with_eol = OutputMatch::Cram.matches(
+            expected,
+            "plain\n12 files\n/tmp/abc/out-1.txt\n\x1b[1mbold\t!\nlast\n",
+        );
+        let missing_line = OutputMatch::Cram.matches(expected, "plain\n");
+
+        // THEN
+        assert!(matching);
+        assert!(!wrong_regex);
+        assert!(!with_eol);
+        assert!(!missing_line);
+    }
+
+    #[test]
+    fn test_matches_trycmd() {
+        // GIVEN
+        let expected = "Compiling [..] v0.1.0\n\
+                        ...\n\
+                        Finished in [..]s\n";
+
+        // WHEN
+        let matching = OutputMatch::Trycmd.matches(
+            expected,
+            "Compiling foo v0.1.0\nwarning: unused\nnote: here\nFinished in 0.2s\n",
+        );
+        let no_lines =
+            OutputMatch::Trycmd.matches(expected, "Compiling foo v0.1.0\nFinished in 0.2s\n");
+        let wrong_line =
+            OutputMatch::Trycmd.matches(expected, "Compiling foo v0.2.0\nFinished in 0.2s\n");
+        let extra_line =
+            OutputMatch::Trycmd.matches(expected, "Compiling foo v0.1.0\nFinished in 0.2s\ndone\n");
+
+        // THEN
+        assert!(matching);
+        assert!(no_lines);
+        assert!(!wrong_line);
+        assert!(!extra_line);
+    }
+}
@@ -7,13 +7,15 @@
 //! * A [TestReport] is the entirety of all [TestSuiteResult]s
 use derive_builder::Builder;
 use std::process::Output;
+use std::time::Duration;
 
+use crate::types::output_match::OutputMatch;
 use crate::types::testcase::TestCase;
 
 /// The result of a test run.
 ///
 /// This is usually part of a [TestSuiteResult]
-#[derive(Debug, Eq, PartialEq, Clone, Builder)]
+#[derive(Debug, Eq, PartialEq, Clone, Default, Builder)]
 #[builder(setter(into))]
 pub struct TestResult {
     /// The name of the test
@@ -24,6 +26,9 @@ pub struct TestResult {
     /// The stdout that was actually obtained from the run
     #[builder(default)]
     pub actual_stdout: String,
+    /// How the actual outputs are compared to the expected ones
+    #[builder(default)]
+    pub output_match: OutputMatch,
     #[builder(default)]
     /// The stderr that was expected in the [TestCase]
     pub expected_stderr: String,
@@ -36,6 +41,78 @@ pub struct TestResult {
     /// The exit status that was actually obtained from the run
     #[builder(default)]
     pub actual_status: i32,
+    /// The interactive or signal step that failed, if any
+    #[builder(default)]
+    pub interact_failure: Option<InteractFailure>,
+    /// The error that prevented the command from running, if any
+    #[builder(default)]
+    pub error: Option<String>,
+    /// The resource limit that the kernel stopped the command for, if any, which explains a
+    /// failure but is not one in itself
+    #[builder(default)]
+    pub limit_exceeded: Option<String>,
+    /// The maximum time to exit after the last signal that was expected in the [TestCase]
+    #[builder(default)]
+    pub terminate_within: Option<Duration>,
+    /// The time the command actually took to exit after the last signal, if any was sent
+    #[builder(default)]
+    pub termination_time: Option<Duration>,
+    /// The maximum wall time that was expected in the [TestCase]
+    #[builder(default)]
+    pub max_duration: Option<Duration>,
+    /// The maximum peak memory, in bytes, that was expected in the [TestCase]
+    #[builder(default)]
+    pub max_memory: Option<u64>,
+    /// The resources that were actually used by the run
+    #[builder(default)]
+    pub usage: Usage,
+    /// What the services of the suite logged during the run, by service name
+    #[builder(default)]
+    pub service_logs: Vec<(String, String)>,
+    /// The requests received by the mock HTTP server, if any
+    #[builder(default)]
+    pub http_requests: Vec<String>,
+    /// How the requests received by the mock HTTP server differ from the expected ones, if they do
+    #[builder(default)]
+    pub http_failure: Option<String>,
+    /// The calls received by the stubs, if any
+    #[builder(default)]
+    pub calls: Vec<String>,
+    /// How the calls received by the stubs differ from the expected ones, if they do
+    #[builder(default)]
+    pub calls_failure: Option<String>,
+    /// The step of a scenario that failed, if any
+    #[builder(default)]
+    pub failed_step: Option<String>,
+    /// Why a variable could not be captured from the stdout, if one could not
+    #[builder(default)]
+    pub capture_failure: Option<String>,
+}
+
+/// The resources used by a command
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Usage {
+    /// The time elapsed between the start and the end of the command
+    pub wall_time: Duration,
+    /// The CPU time spent in user mode
+    pub user_time: Duration,
+    /// The CPU time spent in kernel mode
+    pub system_time: Duration,
+    /// The peak resident set size, in bytes
+    pub max_rss: u64,
+}
+
+/// The description of a failed [InteractStep](crate::types::testcase::InteractStep)
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct InteractFailure {
+    /// The 1-based index of the step that failed
+    pub index: usize,
+    /// A description of the step that failed
+    pub step: String,
+    /// Why the step failed
+    pub reason: String,
+    /// Everything that was read from and written to the command until the failure
+    pub transcript: String,
 }
 
 /// A collection of [TestResult]s
@@ -65,11 +142,40 @@ impl TestResult {
             name: testcase.name,
             expected_stdout: testcase.stdout,
             actual_stdout: String::from_utf8(output.stdout).unwrap(),
+            output_match: testcase.output_match,
             expected_stderr: testcase.stderr,
             actual_stderr: String::from_utf8(output.stderr).unwrap(),
             expected_status: testcase.status,
             // FIXME: ugly, wait for unix_process_wait_more in future versions
             actual_status: output.status.code().unwrap_or(129),
+            interact_failure: None,
+            error: None,
+            limit_exceeded: None,
+            terminate_within: testcase.terminate_within,
+            termination_time: None,
+            max_duration: testcase.max_duration,
+            max_memory: testcase.max_memory,
+            usage: Usage::default(),
+            service_logs: vec![],
+            http_requests: vec![],
+            http_failure: None,
+            calls: vec![],
+            calls_failure: None,
+            failed_step: None,
+            capture_failure: None,
+        }
+    }
+
+    /// Construct a failed [TestResult] from a [TestCase] that could not be executed
+    pub fn from_error(testcase: TestCase, error: anyhow::Error) -> TestResult {
+        TestResult {
+            name: testcase.name,
+            expected_stdout: testcase.stdout,
+            output_match: testcase.output_match,
+            expected_stderr: testcase.stderr,
+            expected_status: testcase.status,
+            error: Some(format!("{:#}", error)),
+            ..Default::default()
         }
     }
 
@@ -79,8 +185,29 @@ impl TestResult {
     /// expected and actual values.
     pub fn is_successful(&self) -> bool {
         self.expected_status == self.actual_status
-            && self.expected_stdout == self.actual_stdout
-            && self.expected_stderr == self.actual_stderr
+            && self
+                .output_match
+                .matches(&self.expected_stdout, &self.actual_stdout)
+            && self
+                .output_match
+                .matches(&self.expected_stderr, &self.actual_stderr)
+            && self.interact_failure.is_none()
+            && self.error.is_none()
+            && self.http_failure.is_none()
+            && self.calls_failure.is_none()
+            && self.capture_failure.is_none()
+            && self.is_within_budget()
+    }
+
+    /// Calculates whether the run stayed within the expected time and memory
+    pub fn is_within_budget(&self) -> bool {
+        self.terminate_within.is_none_or(|max| {
+            self.termination_time
+                .is_some_and(|termination_time| termination_time <= max)
+        }) && self
+            .max_duration
+            .is_none_or(|max| self.usage.wall_time <= max)
+            && self.max_memory.is_none_or(|max| self.usage.max_rss <= max)
     }
 }
 
@@ -97,11 +224,12 @@ mod tests {
         // GIVEN
         let testcase = TestCase {
             name: "mysuite::mycase".to_string(),
-            cmd: "echo -n".to_string(),
+            cmd: "echo -n".into(),
             stdin: "my string".to_string(),
             stdout: "expected stdout".to_string(),
             stderr: "expected stderr".to_string(),
             status: 0,
+            ..Default::default()
         };
 
         let output = Output {
@@ -119,14 +247,55 @@ mod tests {
                 expected_stdout: "expected stdout".to_string(),
                 expected_stderr: "expected stderr".to_string(),
                 actual_stdout: "actual stdout".to_string(),
+                output_match: OutputMatch::Exact,
                 actual_stderr: "actual stderr".to_string(),
                 expected_status: 0,
                 actual_status: 1,
+                interact_failure: None,
+                error: None,
+                limit_exceeded: None,
+                terminate_within: None,
+                termination_time: None,
+                max_duration: None,
+                max_memory: None,
+                usage: Usage::default(),
+                service_logs: vec![],
+                http_requests: vec![],
+                http_failure: None,
+                calls: vec![],
+                calls_failure: None,
+                failed_step: None,
+                capture_failure: None,
             },
             result
         );
     }
 
+    #[test]
+    fn test_construct_result_from_error() {
+        // GIVEN
+        let testcase = TestCase {
+            name: "mysuite::mycase".to_string(),
+            stdout: "expected stdout".to_string(),
+            ..Default::default()
+        };
+
+        // WHEN
+        let result = TestResult::from_error(testcase, anyhow::anyhow!("no such program"));
+
+        // THEN
+        assert_eq!(
+            TestResultBuilder::default()
+                .name("mysuite::mycase")
+                .expected_stdout("expected stdout")
+                .error("no such program".to_string())
+                .build()
+                .unwrap(),
+            result
+        );
+        assert_eq!(false, result.is_successful());
+    }
+
     #[test]
     fn test_is_successful() {
         // GIVEN
@@ -135,13 +304,98 @@ mod tests {
             expected_stdout: "expected stdout".to_string(),
             expected_stderr: "expected stderr".to_string(),
             actual_stdout: "expected stdout".to_string(),
+            output_match: OutputMatch::Exact,
             actual_stderr: "expected stderr".to_string(),
             expected_status: 0,
             actual_status: 0,
+            interact_failure: None,
+            error: None,
+            limit_exceeded: None,
+            terminate_within: None,
+            termination_time: None,
+            max_duration: None,
+            max_memory: None,
+            usage: Usage::default(),
+            service_logs: vec![],
+            http_requests: vec![],
+            http_failure: None,
+            calls: vec![],
+            calls_failure: None,
+            failed_step: None,
+            capture_failure: None,
         };
         // WHEN
         let successful = result.is_successful();
         // THEN
         assert_eq!(true, successful);
     }
+
+    #[test]
+    fn test_is_not_successful_over_budget() {
+        // GIVEN
+        let result = TestResultBuilder::default()
+            .name("mytestcase")
+            .max_duration(Duration::from_secs(1))
+            .max_memory(1024u64)
+            .usage(Usage {
+                wall_time: Duration::from_millis(500),
+                max_rss: 2048,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        // WHEN
+        let successful = result.is_successful();
+        // THEN
+        assert_eq!(false, successful);
+    }
+
+    #[test]
+    fn test_is_not_successful_without_termination() {
+        // GIVEN
+        let result = TestResultBuilder::default()
+            .name("mytestcase")
+            .terminate_within(Duration::from_secs(1))
+            .build()
+            .unwrap();
+        // WHEN
+        let successful = result.is_successful();
+        // THEN
+        assert_eq!(false, successful);
+    }
+
+    #[test]
+    fn test_is_successful_with_limit_exceeded() {
+        // GIVEN
+        let result = TestResultBuilder::default()
+            .name("mytestcase")
+            .expected_status(152)
+            .actual_status(152)
+            .limit_exceeded(Some("cpu time limit (1s)".to_string()))
+            .build()
+            .unwrap();
+        // WHEN
+        let successful = result.is_successful();
+        // THEN
+        assert_eq!(true, successful);
+    }
+
+    #[test]
+    fn test_is_not_successful_with_interact_failure() {
+        // GIVEN
+        let result = TestResultBuilder::default()
+            .name("mytestcase")
+            .interact_failure(InteractFailure {
+                index: 1,
+                step: "expect \"> \"".to_string(),
+                reason: "timed out after 5s".to_string(),
+                transcript: "".to_string(),
+            })
+            .build()
+            .unwrap();
+        // WHEN
+        let successful = result.is_successful();
+        // THEN
+        assert_eq!(false, successful);
+    }
 }
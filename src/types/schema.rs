@@ -0,0 +1,90 @@
+//! # JSON Schema
+//!
+//! The JSON Schema of the documents of test files, derived from the types of [testcase], so that
+//! editors can complete and validate test files as they are written. With the YAML language
+//! server, a test file uses it with a `# yaml-language-server: $schema=<path>` comment.
+//!
+//! [testcase]: crate::types::testcase
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+
+use crate::types::testcase::{SuiteConfig, TestCase};
+
+/// A document of a test file: either the settings of its suite, or a test case
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum Document {
+    Suite(Box<SuiteDocument>),
+    TestCase(Box<TestCase>),
+}
+
+/// The document with the settings of the suite, which comes first in its file
+#[derive(JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)]
+struct SuiteDocument {
+    suite: SuiteConfig,
+}
+
+/// The JSON Schema of a document of a test file
+pub fn schema() -> RootSchema {
+    schema_for!(Document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::schema::{ObjectValidation, Schema};
+
+    fn object<'a>(schema: &'a RootSchema, name: &str) -> &'a ObjectValidation {
+        match &schema.definitions[name] {
+            Schema::Object(object) => object.object.as_ref().unwrap(),
+            _ => panic!("`{}` is not an object", name),
+        }
+    }
+
+    fn keys(value: serde_yaml::Value) -> Vec<String> {
+        let mut keys = value
+            .as_mapping()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key.as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_schema_has_all_fields() {
+        // GIVEN
+        let test_case = serde_yaml::to_value(TestCase::default()).unwrap();
+        let suite = serde_yaml::to_value(SuiteConfig::default()).unwrap();
+
+        // WHEN
+        let schema = schema();
+
+        // THEN
+        let test_case_schema = object(&schema, "TestCase");
+        assert_eq!(
+            keys(test_case),
+            test_case_schema
+                .properties
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["name"],
+            test_case_schema.required.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            keys(suite),
+            object(&schema, "SuiteConfig")
+                .properties
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! # Signals
+//!
+//! A [Signal] can be written in test files either by name (`SIGINT`, `INT`) or by number (`2`).
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+];
+
+/// A unix signal
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Signal(pub libc::c_int);
+
+impl Signal {
+    /// Look up a signal by its name, with or without the `SIG` prefix
+    pub fn from_name(name: &str) -> Option<Signal> {
+        let name = name.trim().to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, number)| Signal(*number))
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SIGNALS.iter().find(|(_, number)| *number == self.0) {
+            Some((name, _)) => write!(f, "SIG{}", name),
+            None => write!(f, "signal {}", self.0),
+        }
+    }
+}
+
+/// A signal number, or a name such as `SIGINT` or `INT`
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RawSignal {
+    Number(libc::c_int),
+    Name(String),
+}
+
+impl<'de> Deserialize<'de> for Signal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RawSignal::deserialize(deserializer)? {
+            RawSignal::Number(number) => Ok(Signal(number)),
+            RawSignal::Name(name) => Signal::from_name(&name)
+                .ok_or_else(|| de::Error::custom(format!("unknown signal `{}`", name))),
+        }
+    }
+}
+
+impl JsonSchema for Signal {
+    fn schema_name() -> String {
+        "Signal".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RawSignal::json_schema(gen)
+    }
+}
+
+impl Serialize for Signal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(Signal(libc::SIGINT)), Signal::from_name("SIGINT"));
+        assert_eq!(Some(Signal(libc::SIGTERM)), Signal::from_name("term"));
+        assert_eq!(None, Signal::from_name("SIGFOO"));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("SIGKILL", Signal(libc::SIGKILL).to_string());
+    }
+}
@@ -0,0 +1,106 @@
+//! # Sizes
+//!
+//! Helpers to read amounts of memory from test files. A size is either a plain number of bytes
+//! (`1048576`) or a number followed by a binary unit (`64k`, `512M`, `1G`, `1GiB`).
+//!
+//! The functions in this module are meant to be used with `#[serde(with = "...")]`, and
+//! `#[schemars(schema_with = "...::schema")]`.
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// Parse a size such as `64k`, `512M` or `1G`
+pub fn parse(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid size `{}`", text))?;
+    let unit = unit.trim().trim_end_matches("iB").trim_end_matches('B');
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("invalid size unit in `{}`", text)),
+    };
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Format a size in a human readable way, for example `1.5 MiB`
+pub fn format(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+/// A number of bytes, or a number followed by a binary unit such as `k`, `M` or `GiB`
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+#[schemars(rename = "Size")]
+enum RawSize {
+    Bytes(u64),
+    Text(String),
+}
+
+/// Deserialize an optional size, in bytes
+pub mod option {
+    use super::*;
+
+    /// Deserialize an optional size
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Ok(match Option::<RawSize>::deserialize(deserializer)? {
+            None => None,
+            Some(RawSize::Bytes(bytes)) => Some(bytes),
+            Some(RawSize::Text(text)) => Some(parse(&text).map_err(de::Error::custom)?),
+        })
+    }
+
+    /// Serialize an optional size as a number of bytes
+    pub fn serialize<S: Serializer>(size: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match size {
+            Some(size) => serializer.serialize_u64(*size),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// The JSON Schema of an optional size
+    pub fn schema(gen: &mut SchemaGenerator) -> Schema {
+        Option::<RawSize>::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(1024), parse("1024"));
+        assert_eq!(Ok(64 * 1024), parse("64k"));
+        assert_eq!(Ok(512 * 1024 * 1024), parse("512M"));
+        assert_eq!(Ok(1024 * 1024 * 1024), parse("1GiB"));
+        assert!(parse("lots").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("12 B", format(12));
+        assert_eq!("1.5 MiB", format(3 << 19));
+    }
+}
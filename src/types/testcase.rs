@@ -7,17 +7,44 @@
 //! * A [TestSuiteCollection] is the entirety of all the [TestSuite]s, typically all YAML files in a
 //!   directory.
 
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 pub use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+use crate::types::duration;
+use crate::types::output_match::OutputMatch;
+use crate::types::signal::Signal;
+use crate::types::size;
+use crate::types::vars::Vars;
 
 /// The specification for a test run.
 ///
 /// This is usually part of a [TestSuite]
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct TestCase {
     /// The name of the test case
     pub name: String,
+    /// The template, or the test case of the file, whose fields this one inherits and overrides
+    #[serde(default)]
+    pub extends: Option<String>,
     /// The command to be executed
-    pub cmd: String,
+    #[serde(default)]
+    pub cmd: Cmd,
+    /// A multi-line script to be executed by `shell` instead of `cmd`
+    #[serde(default)]
+    pub script: Option<String>,
+    /// The interpreter for `cmd` and `script`, when not the suite's or `/bin/sh -c`
+    #[serde(default)]
+    pub shell: Option<Shell>,
+    /// Environment variables to set for the command
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
     /// The stdin that will be fed to the command
     #[serde(default)]
     pub stdin: String,
@@ -27,20 +54,929 @@ pub struct TestCase {
     /// The expected stderr after the test case is executed
     #[serde(default)]
     pub stderr: String,
+    /// How the actual stdout and stderr are compared to `stdout` and `stderr`
+    #[serde(default)]
+    pub output_match: OutputMatch,
     /// The expected exit status after the test case is executed
     #[serde(default)]
     pub status: i32,
+    /// A script of steps to run against the live command, after `stdin` has been written
+    #[serde(default)]
+    pub interact: Vec<InteractStep>,
+    /// The resource limits of the command
+    #[serde(default)]
+    pub limits: Limits,
+    /// Signals to send to the command while it runs, after `interact` if any
+    #[serde(default)]
+    pub signals: Vec<SignalStep>,
+    /// The maximum time the command may take to exit after the last signal was sent
+    #[serde(default, with = "duration::option")]
+    #[schemars(schema_with = "duration::option::schema")]
+    pub terminate_within: Option<Duration>,
+    /// The maximum wall time that the command may take
+    #[serde(default, with = "duration::option")]
+    #[schemars(schema_with = "duration::option::schema")]
+    pub max_duration: Option<Duration>,
+    /// The maximum peak memory that the command may use, in bytes
+    #[serde(default, with = "size::option")]
+    #[schemars(schema_with = "size::option::schema")]
+    pub max_memory: Option<u64>,
+    /// A mock HTTP server to run while the command runs
+    #[serde(default)]
+    pub mock_http: Option<MockHttp>,
+    /// Fake programs to put first on the `PATH` of the command, by name
+    #[serde(default)]
+    pub stubs: BTreeMap<String, Stub>,
+    /// The calls that the command must make to its `stubs`, in order
+    #[serde(default)]
+    pub expect_calls: Option<Vec<ExpectedCall>>,
+    /// The directory in which to run the command, instead of the current one
+    #[serde(default)]
+    pub workdir: Option<String>,
+    /// Commands to run one after the other instead of `cmd`, each with its own expectations
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    /// Regexes extracting variables from the stdout, for the following steps and tests, by name
+    #[serde(default)]
+    pub capture: BTreeMap<String, String>,
+    /// Variables whose combinations of values each make a test case of their own
+    #[serde(default)]
+    pub matrix: Option<Matrix>,
+    /// Rows of inputs and expectations, each making a test case of its own with the same command
+    #[serde(default)]
+    pub cases: Option<Cases>,
+    /// Input files, each making a test case of its own fed with the file
+    #[serde(default)]
+    pub for_each: Option<ForEach>,
+}
+
+impl TestCase {
+    /// Apply `f` to every text that can reference variables
+    ///
+    /// These are the command, the input, the expectations and the environment, including the ones
+    /// of the steps.
+    pub fn map_text(&mut self, f: impl Fn(&str) -> String) {
+        self.cmd = std::mem::take(&mut self.cmd).map(&f);
+        self.script = self.script.as_deref().map(&f);
+        for text in [&mut self.stdin, &mut self.stdout, &mut self.stderr] {
+            *text = f(text);
+        }
+        for value in self.env.values_mut() {
+            *value = f(value);
+        }
+        for step in &mut self.steps {
+            step.cmd = std::mem::take(&mut step.cmd).map(&f);
+            step.script = step.script.as_deref().map(&f);
+            for text in [&mut step.stdin, &mut step.stdout, &mut step.stderr] {
+                *text = f(text);
+            }
+        }
+    }
+}
+
+/// Resource limits, set on the command before it is executed
+///
+/// Unset limits fall back to the ones of the suite, then to the ones given on the command line.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The maximum CPU time, rounded up to the second
+    #[serde(default, with = "duration::option")]
+    #[schemars(schema_with = "duration::option::schema")]
+    pub cpu: Option<Duration>,
+    /// The maximum size of the address space, in bytes
+    #[serde(default, with = "size::option")]
+    #[schemars(schema_with = "size::option::schema")]
+    pub memory: Option<u64>,
+    /// The maximum size of a written file, in bytes
+    #[serde(default, with = "size::option")]
+    #[schemars(schema_with = "size::option::schema")]
+    pub file_size: Option<u64>,
+    /// The maximum number of open file descriptors
+    #[serde(default)]
+    pub open_files: Option<u64>,
+    /// The maximum number of processes of the user
+    #[serde(default)]
+    pub processes: Option<u64>,
+}
+
+impl Limits {
+    /// Fill the unset limits with the ones of `defaults`
+    pub fn or(self, defaults: &Limits) -> Limits {
+        Limits {
+            cpu: self.cpu.or(defaults.cpu),
+            memory: self.memory.or(defaults.memory),
+            file_size: self.file_size.or(defaults.file_size),
+            open_files: self.open_files.or(defaults.open_files),
+            processes: self.processes.or(defaults.processes),
+        }
+    }
+}
+
+/// A command to execute
+///
+/// In YAML, a string is run through `/bin/sh -c`, while a list is executed directly as an argv,
+/// without any shell.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Cmd {
+    /// A command line interpreted by the shell
+    Shell(String),
+    /// A program and its arguments, executed directly
+    Argv(Vec<String>),
+}
+
+impl Cmd {
+    /// Apply `f` to the command line, or to each argument of an argv
+    pub fn map(self, f: impl Fn(&str) -> String) -> Cmd {
+        match self {
+            Cmd::Shell(cmd) => Cmd::Shell(f(&cmd)),
+            Cmd::Argv(argv) => Cmd::Argv(argv.iter().map(|arg| f(arg)).collect()),
+        }
+    }
+}
+
+impl Default for Cmd {
+    fn default() -> Self {
+        Cmd::Shell(String::new())
+    }
+}
+
+impl From<&str> for Cmd {
+    fn from(cmd: &str) -> Self {
+        Cmd::Shell(cmd.to_string())
+    }
+}
+
+impl From<String> for Cmd {
+    fn from(cmd: String) -> Self {
+        Cmd::Shell(cmd)
+    }
+}
+
+/// One of the [TestCase::steps] of a scenario
+///
+/// The steps of a [TestCase] share its working directory, shell, environment, limits and stubs.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// A short description of the step, used in reports
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The command to be executed
+    #[serde(default)]
+    pub cmd: Cmd,
+    /// A multi-line script to be executed instead of `cmd`
+    #[serde(default)]
+    pub script: Option<String>,
+    /// The stdin that will be fed to the command
+    #[serde(default)]
+    pub stdin: String,
+    /// The expected stdout of the command
+    #[serde(default)]
+    pub stdout: String,
+    /// The expected stderr of the command
+    #[serde(default)]
+    pub stderr: String,
+    /// The expected exit status of the command
+    #[serde(default)]
+    pub status: i32,
+    /// Regexes extracting variables from the stdout, for the following steps and tests, by name
+    #[serde(default)]
+    pub capture: BTreeMap<String, String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.name, &self.cmd, &self.script) {
+            (Some(name), _, _) => write!(f, "{}", name),
+            (None, _, Some(_)) => write!(f, "script"),
+            (None, Cmd::Shell(cmd), None) => write!(f, "`{}`", cmd),
+            (None, Cmd::Argv(argv), None) => write!(f, "`{}`", argv.join(" ")),
+        }
+    }
+}
+
+/// An interpreter for [TestCase::cmd] and [TestCase::script], such as `bash -euo pipefail -c`
+///
+/// In YAML, it is either a command line, split on whitespace, or a list of arguments.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawShell")]
+pub struct Shell(pub Vec<String>);
+
+/// A command line, split on whitespace, or a list of arguments
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RawShell {
+    Line(String),
+    Argv(Vec<String>),
+}
+
+impl From<RawShell> for Shell {
+    fn from(raw: RawShell) -> Self {
+        match raw {
+            RawShell::Line(line) => Shell(line.split_whitespace().map(String::from).collect()),
+            RawShell::Argv(argv) => Shell(argv),
+        }
+    }
+}
+
+impl JsonSchema for Shell {
+    fn schema_name() -> String {
+        "Shell".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RawShell::json_schema(gen)
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell(vec!["/bin/sh".to_string(), "-c".to_string()])
+    }
+}
+
+impl Shell {
+    /// The arguments needed to run a script file, i.e. the shell without the `c` of its trailing
+    /// flags, such as `-c` or `-ec`
+    pub fn interpreter(&self) -> Vec<String> {
+        let mut interpreter = self.0.clone();
+        if let Some(last) = interpreter.pop() {
+            let is_flags = last.len() > 1
+                && last.starts_with('-')
+                && last[1..].chars().all(|flag| flag.is_ascii_alphabetic());
+            match is_flags && last.contains('c') {
+                true if last == "-c" => {}
+                true => interpreter.push(last.replace('c', "")),
+                false => interpreter.push(last),
+            }
+        }
+        interpreter
+    }
+}
+
+/// A single step of an interactive session
+///
+/// In YAML, it is the bare `send_eof` keyword, or a map with exactly one of `expect`, `send` and
+/// `send_signal`, and no other keys than those of its step.
+///
+/// See [TestCase::interact]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields, try_from = "serde_yaml::Value")]
+pub enum InteractStep {
+    /// Wait until the output of the command contains a text or matches a regex
+    Expect {
+        /// The text, or regex, to wait for
+        expect: String,
+        /// Whether `expect` is a regex rather than a plain text
+        #[serde(default)]
+        regex: bool,
+        /// How long to wait before giving up
+        #[serde(default = "default_expect_timeout", with = "duration")]
+        #[schemars(schema_with = "duration::schema")]
+        timeout: Duration,
+    },
+    /// Write some text to the stdin of the command
+    Send {
+        /// The text to write
+        send: String,
+    },
+    /// Send a signal to the command
+    SendSignal {
+        /// The signal to send
+        send_signal: Signal,
+    },
+    /// Close the stdin of the command
+    SendEof(SendEof),
+}
+
+/// The keys of an [InteractStep] given as a map, before checking that they make a single step
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawInteractStep {
+    #[serde(default)]
+    expect: Option<String>,
+    #[serde(default)]
+    regex: Option<bool>,
+    #[serde(default, with = "duration::option")]
+    timeout: Option<Duration>,
+    #[serde(default)]
+    send: Option<String>,
+    #[serde(default)]
+    send_signal: Option<Signal>,
+}
+
+impl TryFrom<serde_yaml::Value> for InteractStep {
+    type Error = String;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        if value.is_string() {
+            return serde_yaml::from_value(value)
+                .map(InteractStep::SendEof)
+                .map_err(|error| error.to_string());
+        }
+        let raw: RawInteractStep =
+            serde_yaml::from_value(value).map_err(|error| error.to_string())?;
+        let actions = [
+            raw.expect.is_some(),
+            raw.send.is_some(),
+            raw.send_signal.is_some(),
+        ];
+        match actions.iter().filter(|action| **action).count() {
+            0 => {
+                return Err(
+                    "an interact step needs one of `expect`, `send`, `send_signal` or `send_eof`"
+                        .to_string(),
+                )
+            }
+            1 => (),
+            _ => {
+                return Err(
+                    "an interact step has only one of `expect`, `send` and `send_signal`"
+                        .to_string(),
+                )
+            }
+        }
+        match raw {
+            RawInteractStep {
+                expect: Some(expect),
+                regex,
+                timeout,
+                ..
+            } => Ok(InteractStep::Expect {
+                expect,
+                regex: regex.unwrap_or_default(),
+                timeout: timeout.unwrap_or_else(default_expect_timeout),
+            }),
+            RawInteractStep {
+                regex: None,
+                timeout: None,
+                send: Some(send),
+                ..
+            } => Ok(InteractStep::Send { send }),
+            RawInteractStep {
+                regex: None,
+                timeout: None,
+                send_signal: Some(send_signal),
+                ..
+            } => Ok(InteractStep::SendSignal { send_signal }),
+            _ => Err("`regex` and `timeout` only apply to an `expect` step".to_string()),
+        }
+    }
+}
+
+/// A signal to send to a running command, once some conditions are met
+///
+/// See [TestCase::signals]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SignalStep {
+    /// The signal to send
+    pub signal: Signal,
+    /// Wait until the stdout of the command matches this regex before sending the signal
+    #[serde(default)]
+    pub after_stdout: Option<String>,
+    /// How long to wait before sending the signal, after `after_stdout` matched if set
+    #[serde(default, with = "duration::option")]
+    #[schemars(schema_with = "duration::option::schema")]
+    pub after: Option<Duration>,
+    /// How long to wait for `after_stdout` before giving up
+    #[serde(default = "default_expect_timeout", with = "duration")]
+    #[schemars(schema_with = "duration::schema")]
+    pub timeout: Duration,
+}
+
+impl fmt::Display for SignalStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.signal)?;
+        if let Some(pattern) = &self.after_stdout {
+            write!(f, " after stdout matches {:?}", pattern)?;
+        }
+        if let Some(after) = self.after {
+            write!(f, " after {:?}", after)?;
+        }
+        Ok(())
+    }
+}
+
+/// The bare `send_eof` keyword of an [InteractStep]
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SendEof {
+    /// Close the stdin of the command
+    SendEof,
+}
+
+fn default_expect_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl fmt::Display for InteractStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InteractStep::Expect {
+                expect,
+                regex: false,
+                ..
+            } => write!(f, "expect {:?}", expect),
+            InteractStep::Expect { expect, .. } => write!(f, "expect regex {:?}", expect),
+            InteractStep::Send { send } => write!(f, "send {:?}", send),
+            InteractStep::SendSignal { send_signal } => write!(f, "send_signal {}", send_signal),
+            InteractStep::SendEof(_) => write!(f, "send_eof"),
+        }
+    }
+}
+
+/// Settings that apply to all the [TestCase]s of a [TestSuite]
+///
+/// In YAML, these are given in a document with a single `suite` key.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteConfig {
+    /// The default interpreter for the test cases
+    #[serde(default)]
+    pub shell: Option<Shell>,
+    /// The default resource limits for the test cases
+    #[serde(default)]
+    pub limits: Limits,
+    /// Background services to run while the test cases run
+    #[serde(default)]
+    pub services: Vec<Service>,
+    /// Routes of a mock HTTP server, shared by the test cases
+    #[serde(default)]
+    pub mock_http: Option<MockHttp>,
+    /// Variables that the test cases can reference as `${name}`
+    #[serde(default)]
+    pub vars: Vars,
+    /// A command printing more test cases on its stdout, in YAML or JSON
+    #[serde(default)]
+    pub generate: Option<Cmd>,
+    /// Other test files, relative to this one, whose test cases and templates are pulled in
+    #[serde(default, deserialize_with = "one_or_many")]
+    #[schemars(with = "OneOrMany")]
+    pub include: Vec<String>,
+    /// Partial test cases that the test cases can inherit with `extends`, by id
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub templates: BTreeMap<String, serde_yaml::Value>,
+    /// Whether the file is only meant to be included by others, rather than run on its own
+    #[serde(default)]
+    pub include_only: bool,
+}
+
+/// A single string, or a list of them
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Read a single string as a list of one
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// A background process, such as a server, that runs during a whole [TestSuite]
+///
+/// Each service is given a free TCP port in its `PORT` environment variable. The test cases of the
+/// suite see that port as `<NAME>_PORT`, for example `API_PORT` for a service named `api`.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Service {
+    /// The name of the service
+    pub name: String,
+    /// The command that runs the service
+    pub cmd: Cmd,
+    /// The interpreter for `cmd`, when not `/bin/sh -c`
+    #[serde(default)]
+    pub shell: Option<Shell>,
+    /// How to tell that the service is ready to be used
+    #[serde(default)]
+    pub ready: Readiness,
+    /// How long to wait for the service to be ready
+    #[serde(default = "default_ready_timeout", with = "duration")]
+    #[schemars(schema_with = "duration::schema")]
+    pub timeout: Duration,
+}
+
+impl Service {
+    /// The name of the environment variable holding the port of the service
+    pub fn port_variable(&self) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .collect();
+        format!("{}_PORT", name)
+    }
+}
+
+/// The conditions for a [Service] to be ready, all of which must be met
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Readiness {
+    /// Wait until the port of the service accepts TCP connections
+    #[serde(default)]
+    pub port: bool,
+    /// Wait until the stdout of the service matches this regex
+    #[serde(default)]
+    pub stdout: Option<String>,
+    /// Wait until this file exists
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+fn default_ready_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// A fake program with a canned output, which records how it was called
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Stub {
+    /// The stdout of the program
+    #[serde(default)]
+    pub stdout: String,
+    /// The stderr of the program
+    #[serde(default)]
+    pub stderr: String,
+    /// The exit status of the program
+    #[serde(default)]
+    pub status: i32,
+}
+
+/// A call that a command is expected to make to one of its [Stub]s
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedCall {
+    /// The arguments of the call, starting with the name of the stub
+    pub argv: Vec<String>,
+    /// The stdin of the call, not checked when unset
+    ///
+    /// A stub only reads its stdin when one of the calls expected on it has a `stdin`.
+    #[serde(default)]
+    pub stdin: Option<String>,
+}
+
+/// A mock HTTP server, bound to localhost while a [TestCase] runs
+///
+/// The command sees the URL of the server, such as `http://127.0.0.1:4321`, in its
+/// `MOCK_HTTP_URL` environment variable.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MockHttp {
+    /// The canned responses of the server, the first matching route is used
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// The requests that the command must make, in order
+    #[serde(default)]
+    pub expect_requests: Option<Vec<ExpectedRequest>>,
+}
+
+impl MockHttp {
+    /// Add the routes of `defaults` after the ones of this server
+    pub fn or(mut self, defaults: &MockHttp) -> MockHttp {
+        self.routes.extend(defaults.routes.iter().cloned());
+        self
+    }
+}
+
+/// A canned response of a [MockHttp] server
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// The method of the requests to answer
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// The path of the requests to answer, with or without a query string
+    pub path: String,
+    /// The status code of the response
+    #[serde(default = "default_http_status")]
+    pub status: u16,
+    /// The headers of the response
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The body of the response
+    #[serde(default)]
+    pub body: String,
+}
+
+/// A request that a command is expected to make to a [MockHttp] server
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedRequest {
+    /// The method of the request
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// The path of the request, including its query string if any
+    pub path: String,
+    /// The body of the request, not checked when unset
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+impl fmt::Display for ExpectedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_http_status() -> u16 {
+    200
+}
+
+/// Input files of a [TestCase], each with a sibling file of expected output
+///
+/// In YAML, it is either a glob, relative to the file of the suite, or a map with the glob in
+/// `files` and the naming pattern of the expected output files in `stdout`. The pattern can
+/// reference the input file as `${file}` and its name without extension as `${stem}`, and is
+/// relative to the directory of the input file.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawForEach")]
+pub struct ForEach {
+    /// The glob matching the input files
+    pub files: String,
+    /// The naming pattern of the expected output files
+    pub stdout: String,
+}
+
+/// The glob matching the input files, or the glob and the naming pattern of the expected output
+/// files
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RawForEach {
+    Files(String),
+    Full {
+        files: String,
+        #[serde(default = "default_for_each_stdout")]
+        stdout: String,
+    },
+}
+
+impl From<RawForEach> for ForEach {
+    fn from(raw: RawForEach) -> Self {
+        match raw {
+            RawForEach::Files(files) => ForEach {
+                files,
+                stdout: default_for_each_stdout(),
+            },
+            RawForEach::Full { files, stdout } => ForEach { files, stdout },
+        }
+    }
+}
+
+impl JsonSchema for ForEach {
+    fn schema_name() -> String {
+        "ForEach".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RawForEach::json_schema(gen)
+    }
+}
+
+fn default_for_each_stdout() -> String {
+    "${stem}.out".to_string()
+}
+
+/// The table of a table-driven [TestCase]
+///
+/// In YAML, it is either a list of rows, or the path to a CSV file (or a TSV file, with a `.tsv`
+/// extension) relative to the file of the suite. The header of the file names the columns of the
+/// rows.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Cases {
+    /// Rows given inline
+    Rows(Vec<Case>),
+    /// The path to a file of rows
+    File(String),
+}
+
+/// A row of [Cases], overriding the input and expectations of its [TestCase]
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    /// The name of the row, appended to the name of the test case
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The stdin that will be fed to the command
+    #[serde(default)]
+    pub stdin: Option<String>,
+    /// The expected stdout
+    #[serde(default)]
+    pub stdout: Option<String>,
+    /// The expected stderr
+    #[serde(default)]
+    pub stderr: Option<String>,
+    /// The expected exit status
+    #[serde(default)]
+    pub status: Option<i32>,
+}
+
+/// Lists of values for variables, whose cartesian product parametrizes a [TestCase]
+///
+/// In YAML, it is a map of variable names to lists of values, in the order in which they vary in
+/// the names of the test cases, and an optional `exclude` list of combinations to leave out. An
+/// excluded combination only needs to give some of the variables.
+#[derive(Eq, PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "serde_yaml::Mapping", into = "serde_yaml::Mapping")]
+pub struct Matrix {
+    /// The values of each variable, in order
+    pub vars: Vec<(String, Vec<String>)>,
+    /// The combinations to leave out
+    pub exclude: Vec<Vars>,
+}
+
+impl Matrix {
+    /// The combinations of values that are not excluded, the first variable varying the slowest
+    pub fn combinations(&self) -> Vec<Vec<(String, String)>> {
+        let mut combinations = vec![vec![]];
+        for (name, values) in &self.vars {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination: Vec<(String, String)>| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((name.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations.retain(|combination| {
+            !self.exclude.iter().any(|excluded| {
+                excluded
+                    .iter()
+                    .all(|(name, value)| combination.contains(&(name.clone(), value.clone())))
+            })
+        });
+        combinations
+    }
+}
+
+/// The lists of values of the variables, by name, and the combinations to leave out
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct RawMatrix {
+    /// The combinations to leave out, each giving some of the variables
+    #[serde(default)]
+    exclude: Vec<BTreeMap<String, Scalar>>,
+}
+
+/// A value that YAML writes without quotes as well as with them
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum Scalar {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+}
+
+impl JsonSchema for Matrix {
+    fn schema_name() -> String {
+        "Matrix".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = RawMatrix::json_schema(gen).into_object();
+        schema.object().additional_properties = Some(Box::new(gen.subschema_for::<Vec<Scalar>>()));
+        schema.into()
+    }
+}
+
+impl TryFrom<serde_yaml::Mapping> for Matrix {
+    type Error = String;
+
+    fn try_from(mapping: serde_yaml::Mapping) -> Result<Self, Self::Error> {
+        let mut matrix = Matrix::default();
+        for (name, values) in mapping {
+            let name = scalar(&name).ok_or("matrix variable names must be strings")?;
+            if name == "exclude" {
+                matrix.exclude =
+                    serde_yaml::from_value::<Vec<BTreeMap<String, serde_yaml::Value>>>(values)
+                        .map_err(|error| format!("invalid matrix `exclude`: {}", error))?
+                        .into_iter()
+                        .map(|excluded| {
+                            excluded
+                                .into_iter()
+                                .map(|(name, value)| {
+                                    let value = scalar(&value).ok_or_else(|| {
+                                        format!("excluded value of `{}` must be a scalar", name)
+                                    })?;
+                                    Ok((name, value))
+                                })
+                                .collect::<Result<Vars, String>>()
+                        })
+                        .collect::<Result<_, _>>()?;
+                continue;
+            }
+            let values = match values {
+                serde_yaml::Value::Sequence(values) if !values.is_empty() => values
+                    .iter()
+                    .map(|value| {
+                        scalar(value).ok_or_else(|| {
+                            format!("values of matrix variable `{}` must be scalars", name)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                _ => {
+                    return Err(format!(
+                        "matrix variable `{}` needs a non-empty list of values",
+                        name
+                    ))
+                }
+            };
+            matrix.vars.push((name, values));
+        }
+        for excluded in &matrix.exclude {
+            if let Some(name) = excluded
+                .keys()
+                .find(|name| matrix.vars.iter().all(|(known, _)| known != *name))
+            {
+                return Err(format!(
+                    "matrix `exclude` has an unknown variable `{}`",
+                    name
+                ));
+            }
+        }
+        Ok(matrix)
+    }
+}
+
+impl From<Matrix> for serde_yaml::Mapping {
+    fn from(matrix: Matrix) -> Self {
+        let mut mapping = serde_yaml::Mapping::new();
+        for (name, values) in matrix.vars {
+            mapping.insert(name.into(), values.into());
+        }
+        if !matrix.exclude.is_empty() {
+            let exclude = matrix
+                .exclude
+                .into_iter()
+                .map(|excluded| {
+                    serde_yaml::Value::Mapping(
+                        excluded
+                            .into_iter()
+                            .map(|(name, value)| (name.into(), value.into()))
+                            .collect(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            mapping.insert("exclude".into(), exclude.into());
+        }
+        mapping
+    }
+}
+
+/// The text of a string, number or boolean
+fn scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    }
 }
 
 /// A collection of [TestCase]s
 ///
 /// This is usually part of a [TestSuiteCollection]
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
 pub struct TestSuite {
     /// The name of the testsuite, typically the name of the file containing the test cases
     pub name: String,
     /// The test cases that are part of that test suite
     pub tests: Vec<TestCase>,
+    /// The services that run while the test cases run
+    pub services: Vec<Service>,
+    /// A shell in which the test cases run one after the other, if they share one
+    pub shared_shell: Option<SharedShell>,
+}
+
+/// A single shell that runs all the [TestCase]s of a [TestSuite], as in cram
+///
+/// The shell keeps its state, such as its variables and current directory, from one test case to
+/// the next. It runs in a new temporary directory, and its test cases are fed no stdin.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct SharedShell {
+    /// A script that the shell runs before the test cases
+    pub setup: String,
+    /// Whether the stderr of the test cases is expected along with their stdout, as in a terminal
+    pub merge_stderr: bool,
 }
 
 /// A collection of [TestSuite]s
@@ -69,6 +1005,158 @@ impl TestSuiteCollection {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_service_port_variable() {
+        // GIVEN
+        let service = Service {
+            name: "my-api".to_string(),
+            ..Default::default()
+        };
+
+        // WHEN
+        let variable = service.port_variable();
+
+        // THEN
+        assert_eq!("MY_API_PORT", variable);
+    }
+
+    #[test]
+    fn test_shell_interpreter() {
+        // GIVEN
+        let shells = ["sh -c", "bash -ec", "sh -ce", "python3", "node -e"];
+
+        // WHEN
+        let interpreters = shells
+            .iter()
+            .map(|shell| {
+                let shell: Shell = serde_yaml::from_str(shell).unwrap();
+                shell.interpreter().join(" ")
+            })
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_eq!(
+            vec!["sh", "bash -e", "sh -e", "python3", "node -e"],
+            interpreters
+        );
+    }
+
+    #[test]
+    fn test_limits_or() {
+        // GIVEN
+        let limits = Limits {
+            cpu: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let defaults = Limits {
+            cpu: Some(Duration::from_secs(10)),
+            open_files: Some(64),
+            ..Default::default()
+        };
+
+        // WHEN
+        let result = limits.or(&defaults);
+
+        // THEN
+        assert_eq!(
+            Limits {
+                cpu: Some(Duration::from_secs(1)),
+                open_files: Some(64),
+                ..Default::default()
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_matrix_combinations() {
+        // GIVEN
+        let matrix: Matrix = serde_yaml::from_str(
+            "{order: [asc, desc], algo: [merge, quick], size: [10], exclude: [{order: desc, algo: quick}]}",
+        )
+        .unwrap();
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+        // WHEN
+        let combinations = matrix.combinations();
+
+        // THEN
+        assert_eq!(
+            vec![
+                vec![
+                    pair("order", "asc"),
+                    pair("algo", "merge"),
+                    pair("size", "10")
+                ],
+                vec![
+                    pair("order", "asc"),
+                    pair("algo", "quick"),
+                    pair("size", "10")
+                ],
+                vec![
+                    pair("order", "desc"),
+                    pair("algo", "merge"),
+                    pair("size", "10")
+                ],
+            ],
+            combinations
+        );
+    }
+
+    #[test]
+    fn test_matrix_invalid() {
+        // GIVEN
+        let parse = |yaml| {
+            serde_yaml::from_str::<Matrix>(yaml)
+                .unwrap_err()
+                .to_string()
+        };
+
+        // WHEN
+        let empty = parse("{order: []}");
+        let unknown = parse("{order: [asc], exclude: [{algo: merge}]}");
+
+        // THEN
+        assert_eq!(
+            "matrix variable `order` needs a non-empty list of values",
+            empty
+        );
+        assert_eq!("matrix `exclude` has an unknown variable `algo`", unknown);
+    }
+
+    #[test]
+    fn test_interact_step_invalid() {
+        // GIVEN
+        let parse = |yaml| {
+            serde_yaml::from_str::<InteractStep>(yaml)
+                .unwrap_err()
+                .to_string()
+        };
+
+        // WHEN
+        let both = parse("{send: foo, expect: bar}");
+        let typo = parse("{expect: bar, timout: 1s}");
+        let misplaced = parse("{send: foo, timeout: 1s}");
+        let none = parse("{regex: true}");
+        let keyword = parse("send_of");
+
+        // THEN
+        assert_eq!(
+            "an interact step has only one of `expect`, `send` and `send_signal`",
+            both
+        );
+        assert!(typo.starts_with("unknown field `timout`, expected one of"));
+        assert_eq!(
+            "`regex` and `timeout` only apply to an `expect` step",
+            misplaced
+        );
+        assert_eq!(
+            "an interact step needs one of `expect`, `send`, `send_signal` or `send_eof`",
+            none
+        );
+        assert_eq!("unknown variant `send_of`, expected `send_eof`", keyword);
+    }
+
     #[test]
     fn test_collection_from_testsuites() {
         // GIVEN
@@ -76,10 +1164,14 @@ mod tests {
             TestSuite {
                 name: "suite1".to_string(),
                 tests: vec![],
+                services: vec![],
+                shared_shell: None,
             },
             TestSuite {
                 name: "suite2".to_string(),
                 tests: vec![],
+                services: vec![],
+                shared_shell: None,
             },
         ];
 
@@ -93,10 +1185,14 @@ mod tests {
                     TestSuite {
                         name: "suite1".to_string(),
                         tests: vec![],
+                        services: vec![],
+                        shared_shell: None,
                     },
                     TestSuite {
                         name: "suite2".to_string(),
                         tests: vec![],
+                        services: vec![],
+                        shared_shell: None,
                     }
                 ]
             },
@@ -0,0 +1,137 @@
+//! # Variables
+//!
+//! Test cases can reference variables as `${name}`, for example a value captured from the output
+//! of a previous test. References to unknown variables are left as they are, so that `${HOME}` in
+//! a shell command still reaches the shell, while the environment of Pharaoh itself is known as
+//! `${env.HOME}`. A literal `${name}` is written `$${name}`.
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Variables by name
+pub type Vars = BTreeMap<String, String>;
+
+/// Replace the `${name}` references to known variables in `text`, and unescape `$${name}`
+pub fn substitute(text: &str, vars: &Vars) -> String {
+    replace(text, vars, true)
+}
+
+/// Replace the `${name}` references to known variables in `text`, keeping `$${name}` as is
+///
+/// This is meant for a first pass, before [substitute] is called with more variables.
+pub fn resolve(text: &str, vars: &Vars) -> String {
+    replace(text, vars, false)
+}
+
+/// Escape the `${name}` references in `text`, so that it is kept as is by [resolve] and
+/// [substitute]
+pub fn escape(text: &str) -> String {
+    text.replace("${", "$${")
+}
+
+fn replace(text: &str, vars: &Vars, unescape: bool) -> String {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    let reference =
+        REFERENCE.get_or_init(|| Regex::new(r"\$(\$?)\{([A-Za-z_][A-Za-z0-9_.-]*)\}").unwrap());
+    reference
+        .replace_all(text, |captures: &Captures| {
+            let name = &captures[2];
+            match (&captures[1], vars.get(name)) {
+                ("$", _) if unescape => format!("${{{}}}", name),
+                ("$", _) => captures[0].to_string(),
+                (_, Some(value)) => value.clone(),
+                (_, None) => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Extract the values of `rules`, which map variable names to regexes, from `text`
+///
+/// The value of a variable is the first group of its regex if it has one, or the whole match
+/// otherwise. On failure, the reason is returned as an error.
+pub fn capture(rules: &BTreeMap<String, String>, text: &str) -> Result<Vars, String> {
+    rules
+        .iter()
+        .map(|(name, pattern)| {
+            let regex = Regex::new(pattern)
+                .map_err(|error| format!("invalid regex for `{}`: {}", name, error))?;
+            let captures = regex
+                .captures(text)
+                .ok_or_else(|| format!("`{}` did not match {:?}", name, pattern))?;
+            let value = captures.get(1).or_else(|| captures.get(0)).unwrap();
+            Ok((name.clone(), value.as_str().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute() {
+        // GIVEN
+        let vars = Vars::from([("id".to_string(), "42".to_string())]);
+
+        // WHEN
+        let text = substitute("delete ${id} ${HOME} $${id} $id", &vars);
+
+        // THEN
+        assert_eq!("delete 42 ${HOME} ${id} $id", text);
+    }
+
+    #[test]
+    fn test_resolve_then_substitute() {
+        // GIVEN
+        let vars = Vars::from([("dir".to_string(), "/tmp".to_string())]);
+        let captured = Vars::from([("id".to_string(), "42".to_string())]);
+
+        // WHEN
+        let resolved = resolve("${dir}/${id} $${dir}", &vars);
+        let substituted = substitute(&resolved, &captured);
+
+        // THEN
+        assert_eq!("/tmp/${id} $${dir}", resolved);
+        assert_eq!("/tmp/42 ${dir}", substituted);
+    }
+
+    #[test]
+    fn test_escape() {
+        // GIVEN
+        let vars = Vars::from([("id".to_string(), "42".to_string())]);
+        let text = "${id} $${id} $id";
+
+        // WHEN
+        let escaped = escape(text);
+
+        // THEN
+        assert_eq!(text, substitute(&resolve(&escaped, &vars), &vars));
+    }
+
+    #[test]
+    fn test_capture() {
+        // GIVEN
+        let rules = BTreeMap::from([
+            ("id".to_string(), r"created user (\d+)".to_string()),
+            ("token".to_string(), r"[a-f0-9]{8}".to_string()),
+        ]);
+
+        // WHEN
+        let captured = capture(&rules, "created user 42 with token 0badcafe\n");
+        let failed = capture(&rules, "nothing\n");
+
+        // THEN
+        assert_eq!(
+            Ok(Vars::from([
+                ("id".to_string(), "42".to_string()),
+                ("token".to_string(), "0badcafe".to_string()),
+            ])),
+            captured
+        );
+        assert_eq!(
+            Err(r#"`id` did not match "created user (\\d+)""#.to_string()),
+            failed
+        );
+    }
+}
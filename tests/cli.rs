@@ -4,6 +4,7 @@ use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
 use tempfile::tempdir;
 
 fn command_in_tmpdir() -> Result<(assert_cmd::Command, tempfile::TempDir), Box<dyn Error>> {
@@ -198,3 +199,1250 @@ fn test_stdin() -> Result<(), Box<dyn Error>> {
          "#});
     Ok(())
 }
+
+#[test]
+fn test_interact() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: greeter
+        cmd: printf 'name? '; read name; echo "hello $name"
+        interact:
+          - expect: "name? "
+          - send: "world\n"
+          - expect: hello world
+        stdout: |
+          name? hello world
+        ---
+        name: silent
+        cmd: cat
+        interact:
+          - expect: never
+            timeout: 100ms
+    "#}
+        .as_bytes(),
+    )?;
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::greeter ... OK
+            test foo::silent ... FAILED
+
+            failures:
+
+            ---- foo::silent ----
+            interaction failed at step 1 (expect "never"): timed out after 100ms
+            transcript:
+
+            status code differs:
+            expected: 0
+            actual: 129
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_argv() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: argv
+        cmd: [printf, "%s\n", "it's $HOME"]
+        stdout: |
+          it's $HOME
+        ---
+        name: missing program
+        cmd: [pharaoh-no-such-program]
+    "#}
+        .as_bytes(),
+    )?;
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::argv ... OK
+            test foo::missing program ... FAILED
+
+            failures:
+
+            ---- foo::missing program ----
+            error: program `pharaoh-no-such-program` was not found in $PATH
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_shell_and_script() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        suite:
+          shell: /bin/sh -e -c
+        ---
+        name: stops at the first error
+        cmd: false; echo unreachable
+        status: 1
+        ---
+        name: multi-line script
+        script: |
+          for word in a b; do
+            echo "$word"
+          done
+        stdout: |
+          a
+          b
+    "#}
+        .as_bytes(),
+    )?;
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::stops at the first error ... OK
+            test foo::multi-line script ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_limits() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: default limit
+        cmd: ulimit -n
+        stdout: |
+          32
+        ---
+        name: own limit
+        cmd: exec head -c 4096 /dev/zero > big
+        limits:
+          file_size: 1k
+    "#}
+        .as_bytes(),
+    )?;
+    cmd.args(vec!["--limit", "open_files=32"]);
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::default limit ... OK
+            test foo::own limit ... FAILED
+
+            failures:
+
+            ---- foo::own limit ----
+            limit exceeded: file size limit (1.0 KiB)
+            status code differs:
+            expected: 0
+            actual: 129
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_performance_budget() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: too slow
+        cmd: sleep 0.2
+        max_duration: 100ms
+        max_memory: 1G
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert
+        .success()
+        .stderr("")
+        .stdout(predicates::str::is_match(
+            r"test foo::too slow \.\.\. FAILED \(wall 2\d\d\.\d\dms, user .*, sys .*, peak .*\)\n",
+        )?)
+        .stdout(predicates::str::is_match(
+            r"duration exceeded: expected at most 100.00ms, actual 2\d\d\.\d\dms\n",
+        )?);
+    Ok(())
+}
+
+#[test]
+fn test_signals() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: graceful shutdown
+        cmd: trap 'echo bye; exit 0' TERM; echo ready; while true; do sleep 0.01; done
+        signals:
+          - signal: SIGTERM
+            after_stdout: ready
+            after: 50ms
+        terminate_within: 1s
+        stdout: |
+          ready
+          bye
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::graceful shutdown ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_signals_ignored() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: stuck shutdown
+        cmd: trap '' TERM; echo ready; exec sleep 60
+        signals:
+          - signal: SIGTERM
+            after_stdout: ready
+        terminate_within: 200ms
+        stdout: |
+          ready
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.timeout(std::time::Duration::from_secs(10)).assert();
+
+    // THEN
+    let output = assert.success().stderr("").get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("test foo::stuck shutdown ... FAILED"));
+    assert!(output.contains("expected at most 200.00ms after the last signal"));
+    Ok(())
+}
+
+#[test]
+fn test_services() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        suite:
+          services:
+            - name: api
+              cmd: echo "listening on $PORT"; sleep 30
+              ready:
+                stdout: ^listening
+        ---
+        name: sees the port
+        cmd: test "$API_PORT" -gt 0 && echo "got a port"
+        stdout: |
+          got a port
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::sees the port ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_mock_http() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        suite:
+          mock_http:
+            routes:
+              - path: /users
+                headers:
+                  Content-Type: text/plain
+                body: |
+                  alice
+        ---
+        name: lists the users
+        cmd: curl -s "$MOCK_HTTP_URL/users"
+        stdout: |
+          alice
+        ---
+        name: creates a user
+        cmd: curl -s -X POST -d carol "$MOCK_HTTP_URL/users"
+        mock_http:
+          expect_requests:
+            - method: POST
+              path: /users
+              body: bob
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::lists the users ... OK
+            test foo::creates a user ... FAILED
+
+            failures:
+
+            ---- foo::creates a user ----
+            http requests differ: expected request 1 (`POST /users`) to have body "bob", actual "carol"
+            http requests received:
+              POST /users -> 404
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_stubs() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: deploys the current branch
+        script: |
+          branch=$(git rev-parse --abbrev-ref HEAD)
+          echo "$branch" | ssh prod 'cat > deployed'
+          systemctl restart "myapp@$branch"
+        stubs:
+          git:
+            stdout: |
+              main
+          ssh: {}
+          systemctl:
+            status: 1
+            stderr: |
+              Unit myapp@main.service not found.
+        expect_calls:
+          - argv: [git, rev-parse, --abbrev-ref, HEAD]
+          - argv: [ssh, prod, cat > deployed]
+            stdin: |
+              main
+          - argv: [systemctl, restart, myapp@main]
+        status: 1
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::deploys the current branch ... FAILED
+
+            failures:
+
+            ---- foo::deploys the current branch ----
+            stderr differs:
+            --- expected
+            +++ actual
+            +Unit myapp@main.service not found.
+            stub calls received:
+              git rev-parse --abbrev-ref HEAD
+              ssh prod 'cat > deployed'
+              systemctl restart myapp@main
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_steps() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: a notes workflow
+        env:
+          NOTES: notes.txt
+        steps:
+          - cmd: echo "buy milk" >> "$NOTES"
+          - name: list
+            cmd: cat "$NOTES"
+            stdout: |
+              buy milk
+          - name: clear
+            cmd: rm "$NOTES" && cat "$NOTES"
+            stdout: ""
+          - cmd: echo never run
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::a notes workflow ... FAILED
+
+            failures:
+
+            ---- foo::a notes workflow ----
+            step 3 (clear) failed:
+            status code differs:
+            expected: 0
+            actual: 1
+            stderr differs:
+            --- expected
+            +++ actual
+            +cat: notes.txt: No such file or directory
+
+         "#});
+    assert!(!tmp.path().join("notes.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn test_capture() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: create and delete
+        steps:
+          - cmd: echo "created item 1234"
+            stdout: |
+              created item 1234
+            capture:
+              id: item (\d+)
+          - cmd: test -n "${HOME}" && echo "deleted item ${id}"
+            stdout: |
+              deleted item ${id}
+        ---
+        name: the id is still known
+        cmd: echo "item ${id}"
+        stdout: |
+          item 1234
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::create and delete ... OK
+            test foo::the id is still known ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_templating() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        suite:
+          vars:
+            suite: ${suite_dir}/foo.yaml
+        ---
+        name: prints paths
+        cmd: ls "${suite}"; echo '$${suite}'
+        stdout: |
+          ${suite_dir}/foo.yaml
+          $${suite}
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::prints paths ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_shell_variables() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::create_dir(tmp.path().join("sub"))?;
+    fs::write(
+        tmp.path().join("foo.yaml"),
+        indoc! {r#"
+            name: sees its own environment
+            cmd: echo ${HOME}; test "${env.HOME}" = "$PHARAOH_HOME" && echo same
+            env:
+              HOME: /nowhere
+              PHARAOH_HOME: ${env.HOME}
+            stdout: |
+              /nowhere
+              same
+            ---
+            name: runs in its workdir
+            cmd: echo ${PWD}
+            workdir: sub
+            stdout: |
+              ${suite_dir}/sub
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::sees its own environment ... OK
+            test foo::runs in its workdir ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_matrix() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: greets
+        cmd: echo "${greeting}, ${name}"
+        stdout: |
+          ${greeting}, ${name}
+        matrix:
+          greeting: [hello, bye]
+          name: [alice, bob]
+          exclude:
+            - greeting: bye
+              name: alice
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::greets [greeting=hello, name=alice] ... OK
+            test foo::greets [greeting=hello, name=bob] ... OK
+            test foo::greets [greeting=bye, name=bob] ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_cases() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: upper
+        cmd: tr a-z A-Z
+        cases: upper.csv
+    "#}
+        .as_bytes(),
+    )?;
+    let mut file = File::create(tmp.path().join("upper.csv"))?;
+    file.write_all(
+        indoc! {r#"
+        name,stdin,stdout
+        word,hello,HELLO
+        sentence,"hello, world","HELLO, WORLD"
+        wrong,bye,bye
+    "#}
+        .as_bytes(),
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::upper [word] ... OK
+            test foo::upper [sentence] ... OK
+            test foo::upper [wrong] ... FAILED
+
+            failures:
+
+            ---- foo::upper [wrong] ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -bye
+            +BYE
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_for_each() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let mut file = File::create(tmp.path().join("foo.yaml"))?;
+    file.write_all(
+        indoc! {r#"
+        name: reverse
+        cmd: rev
+        for_each: tests/*.in
+    "#}
+        .as_bytes(),
+    )?;
+    fs::create_dir(tmp.path().join("tests"))?;
+    fs::write(tmp.path().join("tests/01.in"), "abc\n")?;
+    fs::write(tmp.path().join("tests/01.out"), "cba\n")?;
+    fs::write(tmp.path().join("tests/02.in"), "${HOME}\n")?;
+    fs::write(tmp.path().join("tests/02.out"), "}EMOH{$\n")?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for foo
+            test foo::reverse [tests/01.in] ... OK
+            test foo::reverse [tests/02.in] ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_directory_format() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::create_dir_all(tmp.path().join("tests/upper"))?;
+    fs::write(tmp.path().join("tests/upper/cmd"), "tr a-z A-Z\n")?;
+    fs::write(tmp.path().join("tests/upper/stdin"), "hello\n")?;
+    fs::write(tmp.path().join("tests/upper/stdout"), "HELLO\n")?;
+    fs::create_dir_all(tmp.path().join("tests/fails"))?;
+    fs::write(tmp.path().join("tests/fails/cmd"), "false\n")?;
+    fs::write(tmp.path().join("tests/fails/status"), "1\n")?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for tests
+            test tests::fails ... OK
+            test tests::upper ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_markdown_bless() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::write(
+        tmp.path().join("README.md"),
+        indoc! {r#"
+        # Greeting
+
+        ```console
+        $ echo hello
+        goodbye
+        $ echo oops >&2; exit 3
+        oops
+        [3]
+        ```
+    "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.args(["--format", "md", "--bless"]).assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for README
+            test README::Greeting ... FAILED
+            test README::Greeting [2] ... OK
+
+            failures:
+
+            ---- README::Greeting ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -goodbye
+            +hello
+
+         "#});
+    assert_eq!(
+        indoc! {r#"
+        # Greeting
+
+        ```console
+        $ echo hello
+        hello
+        $ echo oops >&2; exit 3
+        oops
+        [3]
+        ```
+    "#},
+        fs::read_to_string(tmp.path().join("README.md"))?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_cram() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::write(
+        tmp.path().join("session.t"),
+        indoc! {r#"
+        The shell keeps its state between commands:
+
+          $ greeting=hello
+          $ mkdir sub && cd sub
+          $ echo "$greeting from $(basename "$PWD")"
+          hello from sub
+          $ [ "$TESTTMP/sub" = "$PWD" ] && echo "in TESTTMP"
+          in TESTTMP
+
+        Annotations:
+
+          $ echo "took 12ms"; printf 'no newline'
+          took \d+ms (re)
+          no newline (no-eol)
+          $ ls missing-file
+          ls: *missing-file* (glob)
+          [2]
+          $ echo wrong
+          right
+    "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for session
+            test session::The shell keeps its state between commands ... OK
+            test session::The shell keeps its state between commands [2] ... OK
+            test session::The shell keeps its state between commands [3] ... OK
+            test session::The shell keeps its state between commands [4] ... OK
+            test session::Annotations ... OK
+            test session::Annotations [2] ... OK
+            test session::Annotations [3] ... FAILED
+
+            failures:
+
+            ---- session::Annotations [3] ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -right
+            +wrong
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_trycmd() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::create_dir(tmp.path().join("cmd"))?;
+    for (file, content) in [
+        (
+            "cmd/echo.toml",
+            "bin.name = \"sh\"\nargs = [\"-c\", \"echo took 12ms; echo done >&2; exit 3\"]\nstatus.code = 3\n",
+        ),
+        ("cmd/echo.stdout", "took [..]ms\n"),
+        ("cmd/echo.stderr", "done\n"),
+        (
+            "cmd/stdin.toml",
+            "bin.name = \"tr\"\nargs = \"a-z A-Z\"\nstdin = \"hello\\n\"\n",
+        ),
+        ("cmd/stdin.stdout", "HELLO\n"),
+        (
+            "cmd/session.trycmd",
+            indoc! {r#"
+                $ seq 5
+                1
+                ...
+                5
+                $ ls missing-file
+                ? 2
+                ls: [..]missing-file[..]
+                $ echo wrong
+                right
+            "#},
+        ),
+    ] {
+        fs::write(tmp.path().join(file), content)?;
+    }
+
+    // WHEN
+    let assert = cmd.args(["--format", "trycmd"]).assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for cmd
+            test cmd::echo ... OK
+            test cmd::stdin ... OK
+            Running tests for cmd/session
+            test cmd/session::line 1 ... OK
+            test cmd/session::line 5 ... OK
+            test cmd/session::line 8 ... FAILED
+
+            failures:
+
+            ---- cmd/session::line 8 ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -right
+            +wrong
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_json_and_toml() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::write(
+        tmp.path().join("json.pharaoh.json"),
+        r#"[
+            {"name": "cat should work", "cmd": "cat", "stdin": "a line\n", "stdout": "a line\n"},
+            {"name": "wc counts lines", "cmd": ["wc", "-l"], "stdin": "a\nb\n", "stdout": "3\n"}
+        ]"#,
+    )?;
+    fs::write(
+        tmp.path().join("toml.pharaoh.toml"),
+        indoc! {r#"
+            [suite.vars]
+            greeting = "hello"
+
+            [[test]]
+            name = "echo should work"
+            cmd = "echo ${greeting}"
+            stdout = """
+            hello
+            """
+        "#},
+    )?;
+    fs::write(tmp.path().join("Cargo.toml"), "[package]\nname = \"app\"\n")?;
+    fs::write(tmp.path().join("package.json"), r#"{"name": "app"}"#)?;
+    for ignored in &[
+        "target/debug/x.yaml",
+        "node_modules/x/x.yaml",
+        ".github/ci.yml",
+    ] {
+        let ignored = tmp.path().join(ignored);
+        fs::create_dir_all(ignored.parent().unwrap())?;
+        fs::write(ignored, "on: push\n")?;
+    }
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for json
+            test json::cat should work ... OK
+            test json::wc counts lines ... FAILED
+            Running tests for toml
+            test toml::echo should work ... OK
+
+            failures:
+
+            ---- json::wc counts lines ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -3
+            +2
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_generator() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let generator = tmp.path().join("sizes.pharaoh-gen");
+    fs::write(
+        &generator,
+        indoc! {r#"
+            #!/bin/sh
+            for size in 1 2 3; do
+                printf -- '---\nname: %s lines\ncmd: seq %s | wc -l\nstdout: "%s\\n"\n' \
+                    "$size" "$size" "$size"
+            done
+        "#},
+    )?;
+    fs::set_permissions(&generator, fs::Permissions::from_mode(0o755))?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for sizes
+            test sizes::1 lines ... OK
+            test sizes::2 lines ... OK
+            test sizes::3 lines ... OK
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_include_and_extends() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::write(
+        tmp.path().join("_cli.yaml"),
+        indoc! {r#"
+            suite:
+              include_only: true
+              templates:
+                prints:
+                  cmd: printf '%s\n' "${test_name}"
+            ---
+            name: shared test
+            extends: prints
+            stdout: |
+              shared test
+        "#},
+    )?;
+    fs::write(
+        tmp.path().join("_smoke.yaml"),
+        "name: runs on its own\ncmd: \"true\"\n",
+    )?;
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        indoc! {r#"
+            suite:
+              include: _cli.yaml
+            ---
+            name: own test
+            extends: prints
+            stdout: |
+              own test
+            ---
+            name: overridden test
+            extends: shared test
+            stdout: |
+              shared test
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for _smoke
+            test _smoke::runs on its own ... OK
+            Running tests for suite
+            test suite::shared test ... OK
+            test suite::own test ... OK
+            test suite::overridden test ... FAILED
+
+            failures:
+
+            ---- suite::overridden test ----
+            stdout differs:
+            --- expected
+            +++ actual
+            -shared test
+            +overridden test
+
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_error_locations() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    fs::write(
+        tmp.path().join("bad.yaml"),
+        indoc! {r#"
+            name: typo
+            cmd: "true"
+            stdot: ok
+            ---
+            name: wrong type
+            cmd: "true"
+            status: zero
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.failure().stdout("").stderr(indoc! {r#"
+            Error: Failed to gather tests
+
+            Caused by:
+                `./bad.yaml`, document 1: unknown field `stdot`, did you mean `stdout`? at line 3 column 1
+                `./bad.yaml`, document 2: status: invalid type: string "zero", expected i32 at line 7 column 9
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_lint() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+    cmd.arg("lint");
+
+    fs::create_dir(tmp.path().join("fixtures"))?;
+    fs::write(tmp.path().join("fixtures/input.txt"), "b\na\n")?;
+    fs::write(tmp.path().join("fixtures/old.txt"), "")?;
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        indoc! {r#"
+            name: sorts
+            cmd: sort fixtures/input.txt
+            stdout: |
+              a
+              b
+            ---
+            name: sorts
+            cmd: pharaoh-no-such-program
+            ---
+            name: placeholder
+            cmd: "true"
+            ---
+            name: prints
+            cmd: printf 'done\n'
+            stdout: done
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.failure().stderr("").stdout(indoc! {r#"
+            suite::sorts: 2 test cases have this name [duplicate-name]
+            suite::sorts: program `pharaoh-no-such-program` was not found in $PATH [unknown-program]
+            suite::placeholder: the test runs no command, so it checks nothing [asserts-nothing]
+            suite::prints: the expected stdout does not end with a newline [trailing-newline]
+            ./fixtures/old.txt: no test file or test mentions the fixture [unused-file]
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_lint_json() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+    cmd.args(["lint", "--output", "json"]);
+
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        "name: prints\ncmd: echo done\nstdout: done\n",
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.failure().stderr("").stdout(indoc! {r#"
+            [
+              {
+                "lint": "trailing-newline",
+                "location": "suite::prints",
+                "message": "the expected stdout does not end with a newline"
+              }
+            ]
+         "#});
+    Ok(())
+}
+
+#[test]
+fn test_lint_generators() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+    cmd.arg("lint");
+
+    let generator = tmp.path().join("flags.pharaoh-gen");
+    fs::write(&generator, "#!/bin/sh\ntouch RAN\n")?;
+    fs::set_permissions(&generator, fs::Permissions::from_mode(0o755))?;
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        indoc! {r#"
+            suite:
+              generate: touch RAN
+            ---
+            name: prints
+            cmd: echo done
+            stdout: |
+              done
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            ./flags.pharaoh-gen: the generator is not run, so its tests are not checked [not-checked]
+            ./suite.yaml: the `generate` command is not run, so its tests are not checked [not-checked]
+         "#});
+    assert!(!tmp.path().join("RAN").exists());
+    Ok(())
+}
+
+#[test]
+fn test_lint_clean() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+    cmd.arg("lint");
+
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        "name: prints\ncmd: echo done\nstdout: |\n  done\n",
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout("");
+    Ok(())
+}
+
+#[test]
+fn test_schema() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, _tmp) = command_in_tmpdir()?;
+    cmd.arg("schema");
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    let output = assert.success().stderr("").get_output().stdout.clone();
+    let schema: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!("http://json-schema.org/draft-07/schema#", schema["$schema"]);
+    assert_eq!(
+        "The expected stdout after the test case is executed",
+        schema["definitions"]["TestCase"]["properties"]["stdout"]["description"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_schema_file_is_not_a_test() -> Result<(), Box<dyn Error>> {
+    // GIVEN
+    let (mut cmd, tmp) = command_in_tmpdir()?;
+
+    let schema = Command::cargo_bin(assert_cmd::crate_name!())?
+        .arg("schema")
+        .output()?
+        .stdout;
+    fs::write(tmp.path().join("pharaoh.schema.json"), schema)?;
+    fs::write(
+        tmp.path().join("suite.yaml"),
+        indoc! {r#"
+            # yaml-language-server: $schema=pharaoh.schema.json
+            name: echo works
+            cmd: echo hi
+            stdout: |
+              hi
+        "#},
+    )?;
+
+    // WHEN
+    let assert = cmd.assert();
+
+    // THEN
+    assert.success().stderr("").stdout(indoc! {r#"
+            Running tests for suite
+            test suite::echo works ... OK
+         "#});
+    Ok(())
+}